@@ -0,0 +1,25 @@
+varying vec2 v_vt;
+varying vec4 v_color;
+
+#ifdef VERTEX_SHADER
+attribute vec2 a_pos;
+attribute vec2 a_vt;
+attribute vec4 a_color;
+uniform mat3 u_projection_matrix;
+uniform mat3 u_view_matrix;
+void main() {
+    v_vt = a_vt;
+    v_color = a_color;
+    vec3 pos = u_projection_matrix * u_view_matrix * vec3(a_pos, 1.0);
+    gl_Position = vec4(pos.xy, 0.0, pos.z);
+}
+#endif
+
+#ifdef FRAGMENT_SHADER
+uniform sampler2D u_texture;
+void main() {
+    vec4 light = texture2D(u_texture, v_vt) * v_color;
+    // Added on top of the framebuffer, so only the color matters
+    gl_FragColor = vec4(light.rgb * light.a, 0.0);
+}
+#endif
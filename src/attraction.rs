@@ -0,0 +1,376 @@
+use super::*;
+
+/// How the cabins of an attraction move
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Motion {
+    /// Cabins hang from a vertical wheel of `radius` around `pos`
+    Wheel {
+        radius: f32,
+        rotate_speed: f32, // TODO Angle
+    },
+    /// Cabins go around a platform seen at an angle, an ellipse with `radius` half axes.
+    /// Cabins at the front are scaled up by `perspective` and bob up and down.
+    Carousel {
+        radius: vec2<f32>,
+        rotate_speed: f32,
+        perspective: f32,
+        bob_height: f32,
+    },
+    /// Cabins side by side on one pendulum hanging from `pos`, swinging by
+    /// `amplitude` degrees `freq` times per second
+    Swing {
+        length: f32,
+        amplitude: f32,
+        freq: f32,
+        cabin_spacing: f32,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct AttractionConfig {
+    pub pos: vec2<f32>,
+    /// Pivot of the rotor texture
+    pub origin: vec2<f32>,
+    pub base_shift: vec2<f32>,
+    #[serde(flatten)]
+    pub motion: Motion,
+    pub cabins: usize,
+    pub swing_origin: vec2<f32>,
+    pub swing_freq: f32,
+    pub swing_amplitude: f32,
+    pub crab_pos: vec2<f32>,
+    pub crab_scale: f32,
+    /// Where findable items sit in the cabin
+    pub item_pos: vec2<f32>,
+    pub item_scale: f32,
+    pub queue_step: vec2<f32>,
+    pub max_queue: usize,
+    pub ride_laps: usize,
+}
+
+impl AttractionConfig {
+    /// Radius around `pos` that the cabins can reach
+    pub fn extent(&self) -> f32 {
+        match self.motion {
+            Motion::Wheel { radius, .. } => radius,
+            Motion::Carousel { radius, .. } => radius.x.max(radius.y),
+            Motion::Swing {
+                length,
+                cabin_spacing,
+                ..
+            } => length + cabin_spacing * self.cabins as f32 / 2.0,
+        }
+    }
+}
+
+pub struct AttractionAssets {
+    /// Directory name, used in error messages
+    pub name: String,
+    pub config: Toml<AttractionConfig>,
+    /// Static part drawn behind everything else
+    pub base: ugli::Texture,
+    /// Turns with the wheel or the pendulum, stays still on carousels
+    pub rotor: ugli::Texture,
+    pub cabin: ugli::Texture,
+}
+
+impl geng::asset::Load for AttractionAssets {
+    fn load(manager: &geng::asset::Manager, path: &std::path::Path) -> geng::asset::Future<Self> {
+        let manager = manager.clone();
+        let path = path.to_owned();
+        async move {
+            Ok(Self {
+                name: path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+                config: manager.load(path.join("config.toml")).await?,
+                base: manager.load(path.join("base.png")).await?,
+                rotor: manager.load(path.join("rotor.png")).await?,
+                cabin: manager.load(path.join("cabin.png")).await?,
+            })
+        }
+        .boxed_local()
+    }
+    const DEFAULT_EXT: Option<&'static str> = None;
+}
+
+/// Riders are tracked by [CrabBehavior::Riding] on the crabs themselves
+#[derive(Default)]
+pub struct Cabin {
+    /// Findable item sitting next to the rider
+    pub item: Option<ItemType>,
+}
+
+/// Attraction state, advanced in [Game::update] instead of derived from the clock
+pub struct Attraction {
+    rotation: f32,
+    time: f32,
+    pub cabins: Vec<Cabin>,
+    /// Phase of every cabin at the last update, to notice them passing the boarding point
+    phases: Vec<f32>,
+}
+
+impl Attraction {
+    pub fn new(config: &AttractionConfig) -> Self {
+        let mut result = Self {
+            rotation: 0.0,
+            time: 0.0,
+            cabins: vec![],
+            phases: vec![],
+        };
+        result.resize(config);
+        result
+    }
+
+    /// Keeps the state of the remaining cabins when the config changes
+    pub fn resize(&mut self, config: &AttractionConfig) {
+        self.cabins.resize_with(config.cabins, Cabin::default);
+        self.phases = (0..config.cabins)
+            .map(|cabin| self.phase(config, cabin))
+            .collect();
+    }
+
+    /// Returns the cabins that have just passed the boarding point
+    pub fn update(&mut self, config: &AttractionConfig, delta_time: f32) -> Vec<usize> {
+        if let Motion::Wheel { rotate_speed, .. } | Motion::Carousel { rotate_speed, .. } =
+            config.motion
+        {
+            self.rotation += rotate_speed.to_radians() * delta_time;
+        }
+        self.time += delta_time;
+        let mut arrived = vec![];
+        for cabin in 0..self.phases.len() {
+            let phase = self.phase(config, cabin);
+            // Phase wraps around when passing the boarding point either way
+            if (phase - self.phases[cabin]).abs() > 0.5 {
+                arrived.push(cabin);
+            }
+            self.phases[cabin] = phase;
+        }
+        arrived
+    }
+
+    /// Fraction of a lap, zero at the boarding point
+    fn phase(&self, config: &AttractionConfig, cabin: usize) -> f32 {
+        match config.motion {
+            Motion::Wheel { .. } | Motion::Carousel { .. } => (self.rotation / (2.0 * f32::PI)
+                + cabin as f32 / config.cabins as f32)
+                .rem_euclid(1.0),
+            Motion::Swing { freq, .. } => (self.time * freq).rem_euclid(1.0),
+        }
+    }
+
+    /// Angle around `pos`, the boarding point is at the bottom
+    fn cabin_angle(&self, config: &AttractionConfig, cabin: usize) -> f32 {
+        2.0 * f32::PI * self.phase(config, cabin) - f32::PI / 2.0
+    }
+
+    fn pendulum(&self, config: &AttractionConfig) -> f32 {
+        match config.motion {
+            Motion::Swing {
+                amplitude, freq, ..
+            } => (2.0 * f32::PI * self.time * freq).sin() * amplitude.to_radians(),
+            _ => 0.0,
+        }
+    }
+
+    pub fn rotor_rotation(&self, config: &AttractionConfig) -> f32 {
+        match config.motion {
+            Motion::Wheel { .. } => self.rotation,
+            Motion::Carousel { .. } => 0.0,
+            Motion::Swing { .. } => self.pendulum(config),
+        }
+    }
+
+    pub fn cabin_pos(&self, config: &AttractionConfig, cabin: usize) -> vec2<f32> {
+        let angle = self.cabin_angle(config, cabin);
+        match config.motion {
+            Motion::Wheel { radius, .. } => config.pos + vec2(radius, 0.0).rotate(angle),
+            Motion::Carousel {
+                radius, bob_height, ..
+            } => {
+                let bob = (2.0 * f32::PI * self.time * config.swing_freq + angle).sin();
+                config.pos
+                    + vec2(radius.x * angle.cos(), radius.y * angle.sin())
+                    + vec2(0.0, bob_height * bob)
+            }
+            Motion::Swing {
+                length,
+                cabin_spacing,
+                ..
+            } => {
+                let offset = cabin as f32 - (config.cabins as f32 - 1.0) / 2.0;
+                config.pos + vec2(offset * cabin_spacing, -length).rotate(self.pendulum(config))
+            }
+        }
+    }
+
+    fn cabin_scale(&self, config: &AttractionConfig, cabin: usize) -> f32 {
+        match config.motion {
+            Motion::Carousel { perspective, .. } => {
+                1.0 - perspective * self.cabin_angle(config, cabin).sin()
+            }
+            _ => 1.0,
+        }
+    }
+
+    fn cabin_tilt(&self, config: &AttractionConfig) -> f32 {
+        (2.0 * f32::PI * self.time * config.swing_freq).sin() * config.swing_amplitude.to_radians()
+            + self.pendulum(config)
+    }
+
+    pub fn cabin_transform(&self, config: &AttractionConfig, cabin: usize) -> mat3<f32> {
+        mat3::translate(self.cabin_pos(config, cabin))
+            * mat3::scale_uniform(self.cabin_scale(config, cabin))
+            * mat3::rotate(self.cabin_tilt(config))
+            * mat3::translate(-config.swing_origin)
+    }
+
+    pub fn item_transform(&self, config: &AttractionConfig, cabin: usize) -> mat3<f32> {
+        self.cabin_transform(config, cabin)
+            * mat3::translate(config.item_pos)
+            * mat3::scale_uniform(config.item_scale)
+    }
+
+    /// Back to front, so carousel cabins overlap properly
+    pub fn draw_order(&self, config: &AttractionConfig) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.cabins.len()).collect();
+        order.sort_by_key(|&cabin| -r32(self.cabin_pos(config, cabin).y));
+        order
+    }
+
+    /// Cabin whose sprite of `cabin_size` covers the world position,
+    /// undoing the swing so clicks follow the tilted cabins
+    pub fn cabin_at(
+        &self,
+        config: &AttractionConfig,
+        cabin_size: vec2<f32>,
+        pos: vec2<f32>,
+    ) -> Option<usize> {
+        let bounds = Aabb2::point(vec2::ZERO).extend_symmetric(cabin_size / 2.0);
+        // Front cabins first
+        self.draw_order(config).into_iter().rev().find(|&cabin| {
+            let local = ((pos - self.cabin_pos(config, cabin)) / self.cabin_scale(config, cabin))
+                .rotate(-self.cabin_tilt(config))
+                + config.swing_origin;
+            bounds.contains(local)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(motion: Motion) -> AttractionConfig {
+        AttractionConfig {
+            pos: vec2(100.0, 200.0),
+            origin: vec2::ZERO,
+            base_shift: vec2::ZERO,
+            motion,
+            cabins: 6,
+            swing_origin: vec2(5.0, 20.0),
+            swing_freq: 0.3,
+            swing_amplitude: 10.0,
+            crab_pos: vec2::ZERO,
+            crab_scale: 1.0,
+            item_pos: vec2::ZERO,
+            item_scale: 1.0,
+            queue_step: vec2::ZERO,
+            max_queue: 1,
+            ride_laps: 1,
+        }
+    }
+
+    fn motions() -> [Motion; 3] {
+        [
+            Motion::Wheel {
+                radius: 500.0,
+                rotate_speed: 36.0,
+            },
+            Motion::Carousel {
+                radius: vec2(600.0, 200.0),
+                rotate_speed: 36.0,
+                perspective: 0.3,
+                bob_height: 20.0,
+            },
+            Motion::Swing {
+                length: 400.0,
+                amplitude: 30.0,
+                freq: 0.1,
+                cabin_spacing: 120.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn clicks_on_cabins_find_them() {
+        let cabin_size = vec2(40.0, 60.0);
+        for (index, motion) in motions().into_iter().enumerate() {
+            let config = config(motion);
+            let mut attraction = Attraction::new(&config);
+            for _ in 0..50 {
+                attraction.update(&config, 0.37);
+                for cabin in 0..config.cabins {
+                    // Near a corner, so that tilt and scale have to be undone
+                    let local = cabin_size * 0.4;
+                    let pos =
+                        (attraction.cabin_transform(&config, cabin) * local.extend(1.0)).into_2d();
+                    assert_eq!(
+                        attraction.cabin_at(&config, cabin_size, pos),
+                        Some(cabin),
+                        "motion {index}",
+                    );
+                }
+                let far = config.pos + vec2(0.0, 10.0 * config.extent());
+                assert_eq!(attraction.cabin_at(&config, cabin_size, far), None);
+            }
+        }
+    }
+
+    #[test]
+    fn cabins_stay_within_extent() {
+        for motion in motions() {
+            let config = config(motion);
+            let mut attraction = Attraction::new(&config);
+            for _ in 0..200 {
+                attraction.update(&config, 0.1);
+                for cabin in 0..config.cabins {
+                    let offset = attraction.cabin_pos(&config, cabin) - config.pos;
+                    // Carousel cabins also bob up and down
+                    assert!(offset.len() <= config.extent() + 20.0 + 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_wheel_cabin_boards_once_per_lap() {
+        for motion in motions().into_iter().take(2) {
+            let config = config(motion);
+            let mut attraction = Attraction::new(&config);
+            let mut boarded = vec![0; config.cabins];
+            // 36 degrees per second, so a bit more than one ten second lap
+            for _ in 0..1005 {
+                for cabin in attraction.update(&config, 0.01) {
+                    boarded[cabin] += 1;
+                }
+            }
+            assert_eq!(boarded, vec![1; config.cabins]);
+        }
+    }
+
+    #[test]
+    fn draw_order_is_back_to_front() {
+        let [_, carousel, _] = motions();
+        let config = config(carousel);
+        let attraction = Attraction::new(&config);
+        let heights: Vec<f32> = attraction
+            .draw_order(&config)
+            .into_iter()
+            .map(|cabin| attraction.cabin_pos(&config, cabin).y)
+            .collect();
+        assert!(heights.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+}
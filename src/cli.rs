@@ -0,0 +1,416 @@
+use super::*;
+
+use std::path::{Path, PathBuf};
+
+/// Map tooling that runs without opening a window
+#[derive(clap::Parser)]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Args)]
+pub struct MapPaths {
+    /// Defaults to assets/roads.json
+    #[clap(long)]
+    roads: Option<PathBuf>,
+    /// Defaults to assets/item_positions.json
+    #[clap(long)]
+    items: Option<PathBuf>,
+}
+
+impl MapPaths {
+    fn roads(&self) -> PathBuf {
+        self.roads
+            .clone()
+            .unwrap_or_else(|| run_dir().join("assets").join("roads.json"))
+    }
+
+    fn items(&self) -> PathBuf {
+        self.items
+            .clone()
+            .unwrap_or_else(|| run_dir().join("assets").join("item_positions.json"))
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum MapFile {
+    Roads,
+    ItemPositions,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Print statistics about the road graph and item slots
+    Stats {
+        #[clap(flatten)]
+        paths: MapPaths,
+    },
+    /// Check the road graph, exits with an error on broken edges and prints warnings
+    Validate {
+        #[clap(flatten)]
+        paths: MapPaths,
+    },
+    /// Convert between json, toml and ron, picked by the file extensions
+    Convert {
+        #[clap(value_enum)]
+        kind: MapFile,
+        input: PathBuf,
+        output: PathBuf,
+        #[clap(long)]
+        pretty: bool,
+    },
+    /// Add item slots beside the roads
+    AutoPlace {
+        #[clap(flatten)]
+        paths: MapPaths,
+        /// Defaults to suggestion_count in assets/editor.toml, like the other options
+        #[clap(long)]
+        count: Option<usize>,
+        #[clap(long)]
+        min_distance: Option<f32>,
+        /// Distance from the road center line
+        #[clap(long)]
+        road_offset: Option<f32>,
+        /// Keep this far outside of the attractions
+        #[clap(long)]
+        attraction_margin: Option<f32>,
+        /// Overwrite the item positions file if not specified
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Generate a road graph along the center lines of a walkability mask
+    RoadsFromMask {
+        /// Image as large as the ground, walkable areas are light
+        mask: PathBuf,
+        /// Defaults to assets/roads.json
+        #[clap(long)]
+        output: Option<PathBuf>,
+        /// Defaults to assets/ground.png, used for the world size
+        #[clap(long)]
+        ground: Option<PathBuf>,
+        /// Minimum brightness of walkable pixels
+        #[clap(long, default_value_t = 128)]
+        threshold: u8,
+        /// Maximum distance between nodes along a road
+        #[clap(long, default_value_t = 200.0)]
+        spacing: f32,
+        /// Dead end branches shorter than this are dropped
+        #[clap(long, default_value_t = 100.0)]
+        min_branch: f32,
+    },
+    /// Draw the road graph and item slots to an svg, or a png over the ground image
+    Export {
+        #[clap(flatten)]
+        paths: MapPaths,
+        /// Defaults to assets/ground.png
+        #[clap(long)]
+        ground: Option<PathBuf>,
+        output: PathBuf,
+    },
+}
+
+fn load<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    futures::executor::block_on(file::load_detect(path))
+        .map_err(|e| e.context(format!("Failed to load {}", path.display())))
+}
+
+/// Format picked by the extension like [file::load_detect], json otherwise
+fn save<T: Serialize>(path: &Path, value: &T, pretty: bool) -> anyhow::Result<()> {
+    let text = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") if pretty => toml::to_string_pretty(value)?,
+        Some("toml") => toml::to_string(value)?,
+        Some("ron") if pretty => ron::ser::to_string_pretty(value, default())?,
+        Some("ron") => ron::to_string(value)?,
+        _ if pretty => serde_json::to_string_pretty(value)?,
+        _ => serde_json::to_string(value)?,
+    };
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// Fails on dangling edges, which would make [Roads::update_outgoing] panic
+fn load_roads(path: &Path) -> anyhow::Result<Roads> {
+    let mut roads: Roads = load(path)?;
+    let errors = road_errors(&roads);
+    if !errors.is_empty() {
+        anyhow::bail!("{}: {}", path.display(), errors.join("; "));
+    }
+    fix_roads(&mut roads);
+    let attractions = load_attractions()?;
+    roads.resolve_legacy_entrances(
+        attractions
+            .iter()
+            .map(|(name, config)| (name.as_str(), config.pos)),
+    );
+    Ok(roads)
+}
+
+/// Directory names and configs of the attractions in assets/attractions
+fn load_attractions() -> anyhow::Result<Vec<(String, attraction::AttractionConfig)>> {
+    let attractions = run_dir().join("assets").join("attractions");
+    let names: Vec<String> = load(&attractions.join("_list.ron"))?;
+    names
+        .into_iter()
+        .map(|name| {
+            let config = load(&attractions.join(&name).join("config.toml"))?;
+            Ok((name, config))
+        })
+        .collect()
+}
+
+fn road_errors(roads: &Roads) -> Vec<String> {
+    let mut errors = vec![];
+    for (index, node) in roads.nodes.iter().enumerate() {
+        for &other in node.connected.iter().chain(&node.two_way) {
+            if other >= roads.nodes.len() {
+                errors.push(format!("node {index} is connected to missing node {other}"));
+            }
+        }
+    }
+    errors
+}
+
+fn road_warnings(roads: &Roads) -> Vec<String> {
+    let mut warnings = vec![];
+    let flagged = |flag: fn(&RoadNode) -> bool| -> Vec<NodeId> {
+        (0..roads.nodes.len())
+            .filter(|&index| flag(&roads.nodes[index]))
+            .collect()
+    };
+    let entrances = flagged(|node| node.entrance);
+    if entrances.is_empty() {
+        warnings.push("no entrance nodes, crabs will not arrive".to_owned());
+    }
+    if flagged(|node| node.exit).is_empty() {
+        warnings.push("no exit nodes, crabs will never leave".to_owned());
+    }
+    if flagged(|node| node.attraction.is_some()).is_empty() {
+        warnings.push("no attraction entrance, nobody will ride the attractions".to_owned());
+    }
+    for (index, node) in roads.nodes.iter().enumerate() {
+        if roads.outgoing(index).is_empty() && !node.exit {
+            warnings.push(format!("node {index} is a dead end"));
+        }
+        let mut edges = node.connected.clone();
+        edges.extend(&node.two_way);
+        edges.sort();
+        if edges.windows(2).any(|pair| pair[0] == pair[1]) {
+            warnings.push(format!("node {index} has duplicate edges"));
+        }
+    }
+    let components = connectivity::strongly_connected_components(roads);
+    let sinks = connectivity::sink_components(roads, &components);
+    for (component, &sink) in sinks.iter().enumerate() {
+        let nodes: Vec<NodeId> = (0..roads.nodes.len())
+            .filter(|&index| components[index] == component)
+            .collect();
+        // Single node sinks are already reported as dead ends
+        if sink
+            && sinks.len() > 1
+            && nodes.len() > 1
+            && !nodes.iter().any(|&index| roads.nodes[index].exit)
+        {
+            warnings.push(format!(
+                "nodes {nodes:?} form a loop with no exit that crabs never leave"
+            ));
+        }
+    }
+    if !entrances.is_empty() {
+        let reachable = reachable_from(roads, &entrances);
+        for (index, reachable) in reachable.into_iter().enumerate() {
+            if !reachable {
+                warnings.push(format!("node {index} is not reachable from any entrance"));
+            }
+        }
+    }
+    warnings
+}
+
+fn reachable_from(roads: &Roads, starts: &[NodeId]) -> Vec<bool> {
+    let mut reachable = vec![false; roads.nodes.len()];
+    let mut stack = starts.to_vec();
+    while let Some(node) = stack.pop() {
+        if std::mem::replace(&mut reachable[node], true) {
+            continue;
+        }
+        stack.extend(roads.outgoing(node));
+    }
+    reachable
+}
+
+fn stats(paths: &MapPaths) -> anyhow::Result<()> {
+    let roads = load_roads(&paths.roads())?;
+    let edges = placement::road_edges(&roads);
+    let two_way = edges.iter().filter(|&&(_, _, two_way)| two_way).count();
+    let length: f32 = edges
+        .iter()
+        .map(|&(from, to, _)| (roads.nodes[from].pos - roads.nodes[to].pos).len())
+        .sum();
+    let count = |flag: fn(&RoadNode) -> bool| roads.nodes.iter().filter(|node| flag(node)).count();
+    let components = connectivity::strongly_connected_components(&roads);
+    println!(
+        "Nodes: {}, strongly connected components: {}",
+        roads.nodes.len(),
+        components.iter().max().map_or(0, |&max| max + 1),
+    );
+    println!(
+        "Edges: {} one-way, {two_way} two-way, total length {length:.0}",
+        edges.len() - two_way,
+    );
+    println!(
+        "Entrances: {}, exits: {}, attraction entrances: {}, idle spots: {}",
+        count(|node| node.entrance),
+        count(|node| node.exit),
+        count(|node| node.attraction.is_some()),
+        count(|node| node.idle_spot),
+    );
+
+    let slots: ItemPositions = load(&paths.items())?;
+    println!(
+        "Item slots: {} ({} with allowed types, {} with rotation limits)",
+        slots.len(),
+        slots
+            .iter()
+            .filter(|slot| !slot.allowed_types.is_empty())
+            .count(),
+        slots.iter().filter(|slot| slot.rotation.is_some()).count(),
+    );
+    Ok(())
+}
+
+fn validate(paths: &MapPaths) -> anyhow::Result<()> {
+    let path = paths.roads();
+    let roads: Roads = load(&path)?;
+    let errors = road_errors(&roads);
+    for error in &errors {
+        println!("error: {error}");
+    }
+    if !errors.is_empty() {
+        anyhow::bail!("{} has {} errors", path.display(), errors.len());
+    }
+    let roads = load_roads(&path)?;
+    for warning in road_warnings(&roads) {
+        println!("warning: {warning}");
+    }
+    let _: ItemPositions = load(&paths.items())?;
+    Ok(())
+}
+
+fn convert(kind: MapFile, input: &Path, output: &Path, pretty: bool) -> anyhow::Result<()> {
+    match kind {
+        MapFile::Roads => save(output, &load_roads(input)?, pretty),
+        MapFile::ItemPositions => save(output, &load::<ItemPositions>(input)?, pretty),
+    }
+}
+
+fn auto_place(
+    paths: &MapPaths,
+    count: Option<usize>,
+    min_distance: Option<f32>,
+    road_offset: Option<f32>,
+    attraction_margin: Option<f32>,
+    output: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let editor: EditorConfig = load(&run_dir().join("assets").join("editor.toml"))?;
+    let count = count.unwrap_or(editor.suggestion_count);
+    let min_distance = min_distance.unwrap_or(editor.suggestion_min_distance);
+    let road_offset = road_offset.unwrap_or(editor.suggestion_road_offset);
+    let attraction_margin = attraction_margin.unwrap_or(editor.suggestion_attraction_margin);
+    let roads = load_roads(&paths.roads())?;
+    let mut slots: ItemPositions = load(&paths.items())?;
+    let avoid: Vec<(vec2<f32>, f32)> = load_attractions()?
+        .into_iter()
+        .map(|(_, config)| (config.pos, config.extent() + attraction_margin))
+        .collect();
+    let placed =
+        placement::suggest_item_slots(&roads, &slots, count, min_distance, road_offset, &avoid);
+    println!("Placed {} of {count} item slots", placed.len());
+    slots.extend(placed.into_iter().map(ItemSlot::new));
+    save(&output.unwrap_or_else(|| paths.items()), &slots, false)
+}
+
+fn roads_from_mask(
+    mask: &Path,
+    output: &Path,
+    ground: &Path,
+    threshold: u8,
+    spacing: f32,
+    min_branch: f32,
+) -> anyhow::Result<()> {
+    let mask = image::open(mask)?.to_luma_alpha8();
+    let world_size = export::png_size(ground)?.map(|x| x as f32);
+    let roads = skeleton::roads_from_mask(&mask, threshold, world_size, spacing, min_branch);
+    println!(
+        "Generated {} nodes and {} edges",
+        roads.nodes.len(),
+        placement::road_edges(&roads).len()
+    );
+    save(output, &roads, false)
+}
+
+fn export(paths: &MapPaths, ground: Option<PathBuf>, output: &Path) -> anyhow::Result<()> {
+    let roads = load_roads(&paths.roads())?;
+    let slots: ItemPositions = load(&paths.items())?;
+    let ground = ground.unwrap_or_else(|| run_dir().join("assets").join("ground.png"));
+    match output.extension().and_then(|extension| extension.to_str()) {
+        Some("svg") => std::fs::write(output, export::svg(&roads, &slots, &ground)?)?,
+        Some("png") => {
+            let font = run_dir()
+                .join("assets")
+                .join("font")
+                .join("Pangolin-Regular.ttf");
+            export::png(&roads, &slots, &ground, &font)?.save(output)?;
+        }
+        _ => anyhow::bail!("{} should end with .svg or .png", output.display()),
+    }
+    Ok(())
+}
+
+pub fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Stats { paths } => stats(&paths),
+        Command::Validate { paths } => validate(&paths),
+        Command::Convert {
+            kind,
+            input,
+            output,
+            pretty,
+        } => convert(kind, &input, &output, pretty),
+        Command::AutoPlace {
+            paths,
+            count,
+            min_distance,
+            road_offset,
+            attraction_margin,
+            output,
+        } => auto_place(
+            &paths,
+            count,
+            min_distance,
+            road_offset,
+            attraction_margin,
+            output,
+        ),
+        Command::RoadsFromMask {
+            mask,
+            output,
+            ground,
+            threshold,
+            spacing,
+            min_branch,
+        } => roads_from_mask(
+            &mask,
+            &output.unwrap_or_else(|| run_dir().join("assets").join("roads.json")),
+            &ground.unwrap_or_else(|| run_dir().join("assets").join("ground.png")),
+            threshold,
+            spacing,
+            min_branch,
+        ),
+        Command::Export {
+            paths,
+            ground,
+            output,
+        } => export(&paths, ground, &output),
+    }
+}
@@ -0,0 +1,154 @@
+use super::*;
+
+/// Tarjan's algorithm with an explicit stack, returns the component of every node.
+/// Components are numbered in reverse topological order, so sinks come first.
+pub fn strongly_connected_components(roads: &Roads) -> Vec<usize> {
+    const UNVISITED: usize = usize::MAX;
+    let count = roads.nodes.len();
+    let mut index = vec![UNVISITED; count];
+    let mut low_link = vec![0; count];
+    let mut on_stack = vec![false; count];
+    let mut stack = vec![];
+    let mut component = vec![UNVISITED; count];
+    let mut components = 0;
+    let mut next_index = 0;
+    for root in 0..count {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        // Nodes being visited, with the next outgoing edge to look at
+        let mut calls = vec![(root, 0)];
+        while let Some(&(node, edge)) = calls.last() {
+            if let Some(&next) = roads.outgoing(node).get(edge) {
+                calls.last_mut().unwrap().1 += 1;
+                if index[next] == UNVISITED {
+                    index[next] = next_index;
+                    low_link[next] = next_index;
+                    next_index += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    calls.push((next, 0));
+                } else if on_stack[next] {
+                    low_link[node] = low_link[node].min(index[next]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+            if low_link[node] == index[node] {
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component[member] = components;
+                    if member == node {
+                        break;
+                    }
+                }
+                components += 1;
+            }
+        }
+    }
+    component
+}
+
+/// Whether each component has no edges leaving it, so crabs entering it never get out
+pub fn sink_components(roads: &Roads, components: &[usize]) -> Vec<bool> {
+    let count = components
+        .iter()
+        .map(|&component| component + 1)
+        .max()
+        .unwrap_or(0);
+    let mut sink = vec![true; count];
+    for (node, &component) in components.iter().enumerate() {
+        if roads
+            .outgoing(node)
+            .iter()
+            .any(|&next| components[next] != component)
+        {
+            sink[component] = false;
+        }
+    }
+    sink
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roads(count: usize, edges: &[(NodeId, NodeId)], two_way: &[(NodeId, NodeId)]) -> Roads {
+        let mut roads = Roads {
+            nodes: (0..count).map(|_| RoadNode::new(vec2::ZERO)).collect(),
+            outgoing: vec![],
+        };
+        for &(from, to) in edges {
+            roads.nodes[from].connected.push(to);
+        }
+        for &(from, to) in two_way {
+            roads.nodes[from].two_way.push(to);
+        }
+        roads.update_outgoing();
+        roads
+    }
+
+    #[test]
+    fn cycle_with_tails() {
+        // 4 leads into the cycle 0 -> 1 -> 2, which leaks into the dead end 3
+        let roads = roads(5, &[(0, 1), (1, 2), (2, 0), (2, 3), (4, 0)], &[]);
+        let components = strongly_connected_components(&roads);
+        assert_eq!(components[0], components[1]);
+        assert_eq!(components[1], components[2]);
+        assert_ne!(components[0], components[3]);
+        assert_ne!(components[0], components[4]);
+        assert_ne!(components[3], components[4]);
+        let sinks = sink_components(&roads, &components);
+        assert_eq!(sinks.len(), 3);
+        assert!(sinks[components[3]]);
+        assert!(!sinks[components[0]]);
+        assert!(!sinks[components[4]]);
+    }
+
+    #[test]
+    fn two_sinks() {
+        // 0 branches into the dead end 1 and the two way loop between 2 and 3
+        let roads = roads(4, &[(0, 1), (0, 2)], &[(2, 3)]);
+        let components = strongly_connected_components(&roads);
+        assert_eq!(components[2], components[3]);
+        let sinks = sink_components(&roads, &components);
+        assert_eq!(sinks.len(), 3);
+        assert!(sinks[components[1]]);
+        assert!(sinks[components[2]]);
+        assert!(!sinks[components[0]]);
+    }
+
+    #[test]
+    fn isolated_nodes_are_their_own_sinks() {
+        let roads = roads(3, &[], &[]);
+        let components = strongly_connected_components(&roads);
+        let mut sorted = components.clone();
+        sorted.sort();
+        assert_eq!(sorted, [0, 1, 2]);
+        assert_eq!(sink_components(&roads, &components), [true; 3]);
+    }
+
+    #[test]
+    fn sinks_come_first() {
+        let chain = roads(4, &[(0, 1), (1, 2), (2, 3)], &[]);
+        assert_eq!(strongly_connected_components(&chain), [3, 2, 1, 0]);
+        let reversed = roads(4, &[(3, 2), (2, 1), (1, 0)], &[]);
+        assert_eq!(strongly_connected_components(&reversed), [0, 1, 2, 3]);
+        // Every edge between components points to a lower number
+        let roads = roads(6, &[(0, 1), (1, 0), (1, 2), (3, 1), (3, 4), (4, 5)], &[]);
+        let components = strongly_connected_components(&roads);
+        for node in 0..roads.nodes.len() {
+            for &next in roads.outgoing(node) {
+                assert!(components[next] <= components[node]);
+            }
+        }
+    }
+}
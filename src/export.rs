@@ -0,0 +1,299 @@
+use super::*;
+
+use std::path::Path;
+
+const NODE_RADIUS: f32 = 20.0;
+const SLOT_RADIUS: f32 = 20.0;
+const EDGE_WIDTH: f32 = 10.0;
+const ARROW_SIZE: f32 = 40.0;
+const FONT_SIZE: f32 = 32.0;
+/// Pieces each edge is split into to rasterize the color gradient
+const GRADIENT_STEPS: usize = 16;
+
+/// Same colors as the in-game editor
+fn edge_colors(two_way: bool) -> (Rgba<f32>, Rgba<f32>) {
+    if two_way {
+        (Rgba::MAGENTA, Rgba::MAGENTA)
+    } else {
+        (Rgba::BLUE, Rgba::RED)
+    }
+}
+
+fn node_color(node: &RoadNode) -> Rgba<f32> {
+    if node.entrance {
+        Rgba::GREEN
+    } else if node.exit {
+        Rgba::RED
+    } else if node.attraction.is_some() {
+        Rgba::YELLOW
+    } else if node.idle_spot {
+        Rgba::new(1.0, 0.5, 0.0, 1.0)
+    } else {
+        Rgba::new(0.5, 0.5, 0.5, 1.0)
+    }
+}
+
+const SLOT_COLOR: Rgba<f32> = Rgba {
+    r: 0.0,
+    g: 0.75,
+    b: 1.0,
+    a: 1.0,
+};
+
+fn lerp_color(a: Rgba<f32>, b: Rgba<f32>, t: f32) -> Rgba<f32> {
+    Rgba::new(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+        a.a + (b.a - a.a) * t,
+    )
+}
+
+/// Triangle pointing from `a` to `b`, placed a bit past the middle of the edge
+fn arrow_head(a: vec2<f32>, b: vec2<f32>) -> [vec2<f32>; 3] {
+    let dir = if a == b {
+        vec2::ZERO
+    } else {
+        (b - a).normalize()
+    };
+    let tip = a + (b - a) * 0.6 + dir * ARROW_SIZE / 2.0;
+    let base = tip - dir * ARROW_SIZE;
+    let side = dir.rotate_90() * ARROW_SIZE / 2.0;
+    [tip, base + side, base - side]
+}
+
+fn css(color: Rgba<f32>) -> String {
+    let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "rgb({},{},{})",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
+
+/// Reads the size from the IHDR chunk without decoding the image
+pub fn png_size(path: &Path) -> anyhow::Result<vec2<u32>> {
+    let bytes = std::fs::read(path)?;
+    anyhow::ensure!(
+        bytes.len() >= 24 && bytes.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "{} is not a png image",
+        path.display()
+    );
+    let read = |offset: usize| u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    Ok(vec2(read(16), read(20)))
+}
+
+/// Road graph and item slots over the ground image bounds
+pub fn svg(roads: &Roads, slots: &ItemPositions, ground: &Path) -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+
+    let size = png_size(ground)?.map(|x| x as f32);
+    // World space is y-up, svg is y-down
+    let p = |pos: vec2<f32>| vec2(pos.x, -pos.y);
+    let points = |points: &[vec2<f32>]| {
+        points
+            .iter()
+            .map(|&pos| format!("{},{}", p(pos).x, p(pos).y))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        -size.x / 2.0,
+        -size.y / 2.0,
+        size.x,
+        size.y,
+        size.x,
+        size.y,
+    )?;
+    writeln!(
+        svg,
+        r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#202020"/>"##,
+        -size.x / 2.0,
+        -size.y / 2.0,
+        size.x,
+        size.y,
+    )?;
+
+    let edges = placement::road_edges(roads);
+    writeln!(svg, "<defs>")?;
+    for (index, &(from, to, two_way)) in edges.iter().enumerate() {
+        let (a, b) = (p(roads.nodes[from].pos), p(roads.nodes[to].pos));
+        let (from_color, to_color) = edge_colors(two_way);
+        writeln!(
+            svg,
+            r#"<linearGradient id="edge{index}" gradientUnits="userSpaceOnUse" x1="{}" y1="{}" x2="{}" y2="{}"><stop offset="0" stop-color="{}"/><stop offset="1" stop-color="{}"/></linearGradient>"#,
+            a.x,
+            a.y,
+            b.x,
+            b.y,
+            css(from_color),
+            css(to_color),
+        )?;
+    }
+    writeln!(svg, "</defs>")?;
+    for (index, &(from, to, two_way)) in edges.iter().enumerate() {
+        let (a, b) = (roads.nodes[from].pos, roads.nodes[to].pos);
+        writeln!(
+            svg,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="url(#edge{index})" stroke-width="{EDGE_WIDTH}"/>"#,
+            p(a).x,
+            p(a).y,
+            p(b).x,
+            p(b).y,
+        )?;
+        let (from_color, to_color) = edge_colors(two_way);
+        let color = css(lerp_color(from_color, to_color, 0.6));
+        let mut heads = vec![arrow_head(a, b)];
+        if two_way {
+            heads.push(arrow_head(b, a));
+        }
+        for head in heads {
+            writeln!(
+                svg,
+                r#"<polygon points="{}" fill="{color}"/>"#,
+                points(&head),
+            )?;
+        }
+    }
+    for (index, node) in roads.nodes.iter().enumerate() {
+        let pos = p(node.pos);
+        writeln!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{NODE_RADIUS}" fill="{}"/>"#,
+            pos.x,
+            pos.y,
+            css(node_color(node)),
+        )?;
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="{FONT_SIZE}" fill="white">{index}</text>"#,
+            pos.x + NODE_RADIUS,
+            pos.y - NODE_RADIUS,
+        )?;
+    }
+    for (index, slot) in slots.iter().enumerate() {
+        let pos = p(slot.pos);
+        writeln!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="6"/>"#,
+            pos.x,
+            pos.y,
+            SLOT_RADIUS * slot.scale,
+            css(SLOT_COLOR),
+        )?;
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="{FONT_SIZE}" fill="{}" text-anchor="middle" dominant-baseline="central">{index}</text>"#,
+            pos.x,
+            pos.y,
+            css(SLOT_COLOR),
+        )?;
+    }
+    writeln!(svg, "</svg>")?;
+    Ok(svg)
+}
+
+/// Same as [svg], rasterized on the cpu over the ground image itself
+pub fn png(
+    roads: &Roads,
+    slots: &ItemPositions,
+    ground: &Path,
+    font: &Path,
+) -> anyhow::Result<image::RgbaImage> {
+    use imageproc::{drawing, point::Point};
+
+    let mut image = image::open(ground)?.to_rgba8();
+    let font = ab_glyph::FontVec::try_from_vec(std::fs::read(font)?)?;
+    let size = vec2(image.width(), image.height()).map(|x| x as f32);
+    let px = |pos: vec2<f32>| vec2(pos.x + size.x / 2.0, size.y / 2.0 - pos.y);
+    let pixel = |color: Rgba<f32>| {
+        let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+        image::Rgba([
+            channel(color.r),
+            channel(color.g),
+            channel(color.b),
+            channel(color.a),
+        ])
+    };
+    let polygon = |points: &[vec2<f32>]| -> Vec<Point<i32>> {
+        points
+            .iter()
+            .map(|&pos| {
+                let pos = px(pos).map(|x| x.round() as i32);
+                Point::new(pos.x, pos.y)
+            })
+            .collect()
+    };
+
+    for (from, to, two_way) in placement::road_edges(roads) {
+        let (a, b) = (roads.nodes[from].pos, roads.nodes[to].pos);
+        if a == b {
+            continue;
+        }
+        let (from_color, to_color) = edge_colors(two_way);
+        let side = (b - a).normalize().rotate_90() * EDGE_WIDTH / 2.0;
+        for step in 0..GRADIENT_STEPS {
+            let t0 = step as f32 / GRADIENT_STEPS as f32;
+            let t1 = (step + 1) as f32 / GRADIENT_STEPS as f32;
+            let (p0, p1) = (a + (b - a) * t0, a + (b - a) * t1);
+            let color = lerp_color(from_color, to_color, (t0 + t1) / 2.0);
+            drawing::draw_polygon_mut(
+                &mut image,
+                &polygon(&[p0 + side, p1 + side, p1 - side, p0 - side]),
+                pixel(color),
+            );
+        }
+        let color = pixel(lerp_color(from_color, to_color, 0.6));
+        drawing::draw_polygon_mut(&mut image, &polygon(&arrow_head(a, b)), color);
+        if two_way {
+            drawing::draw_polygon_mut(&mut image, &polygon(&arrow_head(b, a)), color);
+        }
+    }
+    for (index, node) in roads.nodes.iter().enumerate() {
+        let pos = px(node.pos).map(|x| x.round() as i32);
+        drawing::draw_filled_circle_mut(
+            &mut image,
+            (pos.x, pos.y),
+            NODE_RADIUS as i32,
+            pixel(node_color(node)),
+        );
+        drawing::draw_text_mut(
+            &mut image,
+            pixel(Rgba::WHITE),
+            pos.x + NODE_RADIUS as i32,
+            pos.y - NODE_RADIUS as i32 - FONT_SIZE as i32,
+            FONT_SIZE,
+            &font,
+            &index.to_string(),
+        );
+    }
+    for (index, slot) in slots.iter().enumerate() {
+        let pos = px(slot.pos).map(|x| x.round() as i32);
+        let radius = (SLOT_RADIUS * slot.scale) as i32;
+        for width in 0..6 {
+            drawing::draw_hollow_circle_mut(
+                &mut image,
+                (pos.x, pos.y),
+                radius + width - 3,
+                pixel(SLOT_COLOR),
+            );
+        }
+        let label = index.to_string();
+        let (width, height) = drawing::text_size(FONT_SIZE, &font, &label);
+        drawing::draw_text_mut(
+            &mut image,
+            pixel(SLOT_COLOR),
+            pos.x - width as i32 / 2,
+            pos.y - height as i32 / 2,
+            FONT_SIZE,
+            &font,
+            &label,
+        );
+    }
+    Ok(image)
+}
@@ -0,0 +1,144 @@
+use super::*;
+
+/// Accelerates towards `max_speed`, brakes so that it could stop at `crab_desired_gap`
+/// behind the leader, and never moves closer to it than `crab_min_gap`
+pub fn following_speed(
+    current_speed: f32,
+    max_speed: f32,
+    gap: Option<f32>,
+    config: &Config,
+    delta_time: f32,
+) -> f32 {
+    let mut speed = (current_speed + config.crab_acceleration * delta_time).min(max_speed);
+    if let Some(gap) = gap {
+        let stopping_speed =
+            (2.0 * config.crab_braking * (gap - config.crab_desired_gap).max(0.0)).sqrt();
+        speed = speed.min(stopping_speed.max(current_speed - config.crab_braking * delta_time));
+        if delta_time > 0.0 {
+            speed = speed.min((gap - config.crab_min_gap).max(0.0) / delta_time);
+        }
+    }
+    speed.max(0.0)
+}
+
+/// Closest crab ahead on the same edge within `lane`
+pub fn crab_ahead(crabs: &[Crab], crab_index: usize, lane: f32, config: &Config) -> Option<usize> {
+    let crab = &crabs[crab_index];
+    crabs
+        .iter()
+        .enumerate()
+        .filter(|&(other_index, other)| {
+            let ahead = other.position.distance - crab.position.distance;
+            other_index != crab_index
+                && other.position.from == crab.position.from
+                && other.position.to == crab.position.to
+                && (ahead > 0.0 || (ahead == 0.0 && other_index > crab_index))
+                && ahead < config.collision_check_distance
+                && (other.lane - lane).abs() < config.collision_check_radius
+        })
+        .min_by_key(|(_, other)| r32(other.position.distance))
+        .map(|(other_index, _)| other_index)
+}
+
+/// Closest crab ahead in either current or target lane, with the gap to it
+pub fn crab_leader(crabs: &[Crab], crab_index: usize, config: &Config) -> Option<(usize, f32)> {
+    let crab = &crabs[crab_index];
+    crab.position.to?;
+    [crab.lane, crab.target_lane]
+        .into_iter()
+        .filter_map(|lane| crab_ahead(crabs, crab_index, lane, config))
+        .map(|leader| {
+            (
+                leader,
+                crabs[leader].position.distance - crab.position.distance,
+            )
+        })
+        .min_by_key(|&(_, gap)| r32(gap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(include_str!("../assets/config.toml")).unwrap()
+    }
+
+    fn walking_crab(distance: f32, speed: f32) -> Crab {
+        Crab {
+            type_index: 0,
+            position: Position {
+                from: 0,
+                to: Some(1),
+                distance,
+            },
+            next: None,
+            behavior: CrabBehavior::Walking,
+            speed,
+            current_speed: speed,
+            scale: 1.0,
+            flipped: false,
+            heading: vec2(1.0, 0.0),
+            lane: 0.0,
+            target_lane: 0.0,
+            animation_time: 0.0,
+            left_hand: None,
+            right_hand: None,
+        }
+    }
+
+    /// Moves the crabs along one endless edge the way [Game::update] does,
+    /// returning the smallest gap between neighbours seen on any frame
+    fn smallest_gap(crabs: &mut [Crab], frame_times: impl IntoIterator<Item = f32>) -> f32 {
+        let config = config();
+        let mut smallest = f32::INFINITY;
+        for delta_time in frame_times {
+            for crab_index in 0..crabs.len() {
+                let gap = crab_leader(crabs, crab_index, &config).map(|(_, gap)| gap);
+                let crab = &mut crabs[crab_index];
+                crab.current_speed =
+                    following_speed(crab.current_speed, crab.speed, gap, &config, delta_time);
+                crab.position.distance += crab.current_speed * delta_time;
+            }
+            let mut distances: Vec<f32> = crabs.iter().map(|crab| crab.position.distance).collect();
+            distances.sort_by_key(|&distance| r32(distance));
+            for pair in distances.windows(2) {
+                smallest = smallest.min(pair[1] - pair[0]);
+            }
+        }
+        smallest
+    }
+
+    #[test]
+    fn fast_crab_stays_behind_slow_one() {
+        let mut crabs = [walking_crab(0.0, 200.0), walking_crab(100.0, 30.0)];
+        let gap = smallest_gap(&mut crabs, std::iter::repeat(1.0 / 60.0).take(3000));
+        assert!(gap >= config().crab_min_gap - 1e-3, "gap {gap}");
+    }
+
+    #[test]
+    fn crabs_stop_behind_a_stopped_one() {
+        let mut crabs = [
+            walking_crab(200.0, 0.0),
+            walking_crab(0.0, 150.0),
+            walking_crab(-60.0, 250.0),
+        ];
+        let gap = smallest_gap(&mut crabs, std::iter::repeat(1.0 / 60.0).take(3000));
+        assert!(gap >= config().crab_min_gap - 1e-3, "gap {gap}");
+    }
+
+    #[test]
+    fn mixed_speeds_and_frame_times_never_overlap() {
+        let config = config();
+        let speeds = [120.0, 40.0, 200.0, 90.0, 160.0, 10.0, 250.0, 70.0];
+        // A column packed as tightly as allowed, with faster crabs stuck behind slower ones
+        let mut crabs: Vec<Crab> = speeds
+            .iter()
+            .enumerate()
+            .map(|(i, &speed)| walking_crab(i as f32 * config.crab_min_gap, speed))
+            .collect();
+        let frame_times = [1.0 / 144.0, 1.0 / 30.0, 1.0 / 60.0, 0.1];
+        let gap = smallest_gap(&mut crabs, frame_times.into_iter().cycle().take(5000));
+        assert!(gap >= config.crab_min_gap - 1e-3, "gap {gap}");
+    }
+}
@@ -0,0 +1,85 @@
+use super::*;
+
+/// Development helper that reloads config and assets when files change on disk
+pub struct HotReload {
+    geng: Geng,
+    timer: f32,
+    modified: HashMap<std::path::PathBuf, std::time::SystemTime>,
+    loading: Option<std::pin::Pin<Box<dyn std::future::Future<Output = validation::LoadResult>>>>,
+}
+
+impl HotReload {
+    const POLL_INTERVAL: f32 = 0.5;
+
+    /// Only in debug builds on desktop
+    pub fn new(geng: &Geng) -> Option<Self> {
+        if !cfg!(debug_assertions) || cfg!(target_arch = "wasm32") {
+            return None;
+        }
+        let mut result = Self {
+            geng: geng.clone(),
+            timer: 0.0,
+            modified: HashMap::new(),
+            loading: None,
+        };
+        result.rescan();
+        Some(result)
+    }
+
+    fn assets_path() -> std::path::PathBuf {
+        run_dir().join("assets")
+    }
+
+    fn scan(
+        dir: &std::path::Path,
+        modified: &mut HashMap<std::path::PathBuf, std::time::SystemTime>,
+    ) {
+        for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                Self::scan(&path, modified);
+            } else if let Ok(time) = entry.metadata().and_then(|metadata| metadata.modified()) {
+                modified.insert(path, time);
+            }
+        }
+    }
+
+    fn rescan(&mut self) {
+        self.modified.clear();
+        Self::scan(&Self::assets_path(), &mut self.modified);
+    }
+
+    /// Returns freshly loaded base config and assets once a reload finishes
+    pub fn update(&mut self, delta_time: f32) -> Option<(serde_json::Value, Assets)> {
+        if let Some(loading) = &mut self.loading {
+            let result = loading.now_or_never()?;
+            self.loading = None;
+            return match result {
+                Ok(result) => {
+                    log::info!("Reloaded assets");
+                    Some(result)
+                }
+                Err(errors) => {
+                    for error in errors {
+                        log::error!("{error}");
+                    }
+                    None
+                }
+            };
+        }
+
+        self.timer -= delta_time;
+        if self.timer > 0.0 {
+            return None;
+        }
+        self.timer = Self::POLL_INTERVAL;
+        let mut modified = HashMap::new();
+        Self::scan(&Self::assets_path(), &mut modified);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        self.loading = Some(validation::load(self.geng.clone()).boxed_local());
+        None
+    }
+}
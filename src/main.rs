@@ -1,20 +1,55 @@
 use geng::prelude::*;
 
+mod attraction;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+mod connectivity;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+mod following;
+mod hot_reload;
+mod menu;
+mod particles;
+mod placement;
+#[cfg(not(target_arch = "wasm32"))]
+mod skeleton;
+mod validation;
+
 #[derive(Deref)]
 pub struct Toml<T>(#[deref] pub T);
 
 impl<T: DeserializeOwned + 'static> geng::asset::Load for Toml<T> {
     fn load(_manager: &geng::asset::Manager, path: &std::path::Path) -> geng::asset::Future<Self> {
         let path = path.to_owned();
-        async move { Ok(Self(file::load_detect(path).await?)) }.boxed_local()
+        async move {
+            let value = file::load_detect(&path)
+                .await
+                .map_err(|e| e.context(format!("Failed to load {}", path.display())))?;
+            Ok(Self(value))
+        }
+        .boxed_local()
     }
     const DEFAULT_EXT: Option<&'static str> = Some("toml");
 }
 
 enum Drag {
     None,
-    Detecting { from: vec2<f32>, timer: Timer },
-    Dragging { prev_mouse_pos: vec2<f32> },
+    Detecting {
+        from: vec2<f32>,
+        timer: Timer,
+    },
+    Dragging {
+        prev_mouse_pos: vec2<f32>,
+    },
+    /// Editor box selection, started at a world position
+    Selecting {
+        from: vec2<f32>,
+    },
+    /// Editor moving the selected nodes, keeping `anchor` at cursor + `offset`
+    MovingNodes {
+        anchor: NodeId,
+        offset: vec2<f32>,
+    },
 }
 
 #[derive(Deserialize)]
@@ -35,85 +70,674 @@ struct Config {
     pub jump_rotation_amplitude: f32,
     pub collision_check_distance: f32,
     pub collision_check_radius: f32,
-    pub collision_slow_down: f32,
+    pub crab_min_gap: f32,
+    pub crab_desired_gap: f32,
+    pub crab_acceleration: f32,
+    pub crab_braking: f32,
     pub crab_hold_item_probability: f64,
     pub crab_hold_double_item_probability: f64,
     pub crab_left_hand_pos: vec2<f32>,
     pub crab_right_hand_pos: vec2<f32>,
     pub types_to_find: usize,
+    pub crab_idle_probability: f64,
+    /// Used instead of crab_idle_probability on idle spot nodes
+    pub crab_idle_spot_probability: f64,
+    pub crab_min_idle_time: f32,
+    pub crab_max_idle_time: f32,
+    pub crab_ride_probability: f64,
+    pub crab_item_reach: f32,
+    pub crab_drop_item_rate: f64,
+    pub crab_pick_up_item_rate: f64,
+    pub crab_trade_items_rate: f64,
+    pub crab_speed_variation: f32,
+    pub crab_scale_variation: f32,
+    pub road_width: f32,
+    pub lane_change_speed: f32,
+    pub crab_arrival_rate: f64,
+    pub crab_exit_probability: f64,
+    pub target_min_count: usize,
+    pub target_max_count: usize,
+    pub target_in_hand_probability: f64,
+    /// Chance for a target not held by a crab to sit in an attraction cabin
+    pub target_in_cabin_probability: f64,
+    /// Chance for each cabin to hold a distractor item
+    pub cabin_item_probability: f64,
+    pub round_restart_delay: f32,
+    /// Seconds for a whole day and night, zero keeps it day
+    pub day_length: f32,
+    /// Opacity of the night tint at midnight
+    pub night_darkness: f32,
+    pub night_color: Rgba<f32>,
+    /// Tint at sunrise and sunset, fading into night_color
+    pub dusk_color: Rgba<f32>,
+    pub light_color: Rgba<f32>,
+    pub lamp_light_radius: f32,
+    pub cabin_light_radius: f32,
+    /// Chance for each round to be rainy
+    pub rain_probability: f64,
+    /// Raindrops per second
+    pub rain_rate: f32,
+    /// Crab speeds are multiplied by this in the rain
+    pub rain_crab_speed_multiplier: f32,
+    pub rain_umbrella_probability: f64,
+    /// Umbrella canopy relative to the hand holding it
+    pub crab_umbrella_pos: vec2<f32>,
+    pub confetti_count: usize,
+    /// Chance for a hop of a walking crab to raise dust
+    pub dust_probability: f64,
+    /// Seconds a hint keeps sparkling
+    pub hint_duration: f32,
+    /// Seconds before another hint can be asked for
+    pub hint_cooldown: f32,
+}
+
+/// Named partial overrides of [Config]
+#[derive(Deserialize)]
+struct DifficultyPreset {
+    name: String,
+    #[serde(flatten)]
+    overrides: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Difficulties {
+    /// When ramping up, switch to the next preset every this many rounds
+    rounds_per_level: usize,
+    presets: Vec<DifficultyPreset>,
+}
+
+impl Difficulties {
+    /// Merge preset over the base config.toml
+    fn config(&self, base: &serde_json::Value, level: usize) -> serde_json::Result<Config> {
+        let mut value = base.clone();
+        if let (serde_json::Value::Object(fields), Some(preset)) =
+            (&mut value, self.presets.get(level))
+        {
+            fields.extend(preset.overrides.clone());
+        }
+        serde_json::from_value(value)
+    }
+}
+
+/// Road editor tuning, kept apart from [Config] so difficulty presets never touch it
+#[derive(Deserialize)]
+struct EditorConfig {
+    /// Item slots suggested at once
+    pub suggestion_count: usize,
+    pub suggestion_min_distance: f32,
+    /// Distance of suggested slots from the road center line
+    pub suggestion_road_offset: f32,
+    /// Kept free around attractions
+    pub suggestion_attraction_margin: f32,
+    pub grid_size: f32,
+}
+
+/// Player settings, persisted between runs
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    volume: f64,
+    difficulty: usize,
+    ramp_up: bool,
+    zoom_sensitivity: f32,
+    drag_sensitivity: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            volume: 0.5,
+            difficulty: 1,
+            ramp_up: false,
+            zoom_sensitivity: 1.0,
+            drag_sensitivity: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    const PREFERENCES_KEY: &'static str = "find-ferris-settings";
+
+    fn load() -> Self {
+        preferences::load(Self::PREFERENCES_KEY).unwrap_or_default()
+    }
+
+    fn save(&self) {
+        preferences::save(Self::PREFERENCES_KEY, self);
+    }
+}
+
+/// Latest config and assets, shared by the menus and the games started from them
+/// so that hot reloads outlive the game they happened in
+struct Loaded {
+    base_config: serde_json::Value,
+    assets: Rc<Assets>,
 }
 
 type NodeId = usize;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct RoadNode {
     pos: vec2<f32>,
     connected: Vec<NodeId>,
+    /// Edges walkable both ways, stored on one end only
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    two_way: Vec<NodeId>,
+    /// Directory name of the attraction whose queue starts here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attraction: Option<String>,
+    /// Older maps could only mark ferris wheel entrances,
+    /// replaced by [RoadNode::attraction] in [Roads::resolve_legacy_entrances]
+    #[serde(default, skip_serializing)]
+    wheel_entrance: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    entrance: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    exit: bool,
+    /// Crabs are more likely to stop here for a while
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    idle_spot: bool,
+}
+
+/// Per-node metadata editable from the inspector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeFlag {
+    Entrance,
+    Exit,
+    IdleSpot,
 }
 
-#[derive(geng::asset::Load, Serialize, Deserialize)]
+impl NodeFlag {
+    const ALL: [Self; 3] = [Self::Entrance, Self::Exit, Self::IdleSpot];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Entrance => "Entrance",
+            Self::Exit => "Exit",
+            Self::IdleSpot => "Idle spot",
+        }
+    }
+
+    /// Editor key toggling the flag
+    fn key(self) -> geng::Key {
+        match self {
+            Self::Entrance => geng::Key::P,
+            Self::Exit => geng::Key::X,
+            Self::IdleSpot => geng::Key::J,
+        }
+    }
+}
+
+/// What clicking an inspector row does
+#[derive(Debug, Clone, Copy)]
+enum InspectorAction {
+    Toggle(NodeFlag),
+    /// Steps the nodes through the attractions their queue leads to
+    CycleAttraction,
+}
+
+impl RoadNode {
+    pub fn new(pos: vec2<f32>) -> Self {
+        Self {
+            pos,
+            connected: default(),
+            two_way: default(),
+            attraction: None,
+            wheel_entrance: false,
+            entrance: false,
+            exit: false,
+            idle_spot: false,
+        }
+    }
+
+    fn flag(&self, flag: NodeFlag) -> bool {
+        match flag {
+            NodeFlag::Entrance => self.entrance,
+            NodeFlag::Exit => self.exit,
+            NodeFlag::IdleSpot => self.idle_spot,
+        }
+    }
+
+    fn flag_mut(&mut self, flag: NodeFlag) -> &mut bool {
+        match flag {
+            NodeFlag::Entrance => &mut self.entrance,
+            NodeFlag::Exit => &mut self.exit,
+            NodeFlag::IdleSpot => &mut self.idle_spot,
+        }
+    }
+}
+
+#[derive(geng::asset::Load, Clone, Serialize, Deserialize)]
 #[load(json)]
 struct Roads {
     nodes: Vec<RoadNode>,
+    #[serde(skip)]
+    outgoing: Vec<Vec<NodeId>>,
+}
+
+const ITEM_POSITIONS_VERSION: u32 = 1;
+
+fn default_item_scale() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ItemSlot {
+    pos: vec2<f32>,
+    #[serde(default = "default_item_scale")]
+    scale: f32,
+    /// Min and max rotation in degrees, any rotation if not specified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rotation: Option<[f32; 2]>,
+    /// Any type can spawn if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allowed_types: Vec<ItemType>,
+}
+
+impl ItemSlot {
+    pub fn new(pos: vec2<f32>) -> Self {
+        Self {
+            pos,
+            scale: default_item_scale(),
+            rotation: None,
+            allowed_types: vec![],
+        }
+    }
+
+    pub fn allows(&self, item_type: ItemType) -> bool {
+        self.allowed_types.is_empty() || self.allowed_types.contains(&item_type)
+    }
+
+    pub fn random_type(&self, types: usize) -> Option<ItemType> {
+        if self.allowed_types.is_empty() {
+            return Some(thread_rng().gen_range(0..types));
+        }
+        self.allowed_types
+            .iter()
+            .copied()
+            .filter(|&item_type| item_type < types)
+            .choose(&mut thread_rng())
+    }
+
+    pub fn random_rotation(&self) -> f32 {
+        match self.rotation {
+            Some([min, max]) if min < max => thread_rng().gen_range(min..max).to_radians(),
+            Some([min, _]) => min.to_radians(),
+            None => thread_rng().gen_range(0.0..2.0 * f32::PI),
+        }
+    }
+
+    /// Where an item rotated by `rotation` radians is drawn, also used by the editor
+    pub fn transform(&self, rotation: f32) -> mat3<f32> {
+        mat3::translate(self.pos) * mat3::rotate(rotation) * mat3::scale_uniform(self.scale)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ItemPositionsFormat {
+    Legacy(Vec<vec2<f32>>),
+    Versioned { version: u32, slots: Vec<ItemSlot> },
+}
+
+impl TryFrom<ItemPositionsFormat> for ItemPositions {
+    type Error = String;
+    fn try_from(format: ItemPositionsFormat) -> Result<Self, Self::Error> {
+        match format {
+            ItemPositionsFormat::Legacy(positions) => Ok(Self {
+                version: ITEM_POSITIONS_VERSION,
+                slots: positions.into_iter().map(ItemSlot::new).collect(),
+            }),
+            ItemPositionsFormat::Versioned { version, slots } => {
+                if version > ITEM_POSITIONS_VERSION {
+                    return Err(format!(
+                        "item positions version {version} is not supported (latest is {ITEM_POSITIONS_VERSION})"
+                    ));
+                }
+                Ok(Self {
+                    version: ITEM_POSITIONS_VERSION,
+                    slots,
+                })
+            }
+        }
+    }
 }
 
-#[derive(geng::asset::Load, Serialize, Deserialize, Deref, DerefMut)]
+/// Positions of the lamps lighting up the map at night
+#[derive(geng::asset::Load, Clone, Serialize, Deserialize, Deref, DerefMut)]
 #[serde(transparent)]
 #[load(json)]
-struct ItemPositions {
+struct Lamps {
     #[deref]
     positions: Vec<vec2<f32>>,
 }
 
+#[derive(geng::asset::Load, Clone, Serialize, Deserialize, Deref, DerefMut)]
+#[serde(try_from = "ItemPositionsFormat")]
+#[load(json)]
+struct ItemPositions {
+    version: u32,
+    #[deref]
+    slots: Vec<ItemSlot>,
+}
+
 fn fix_roads(roads: &mut Roads) {
     for (index, node) in roads.nodes.iter_mut().enumerate() {
         node.connected.retain(|&other| other != index);
+        node.two_way.retain(|&other| other != index);
     }
+    roads.update_outgoing();
 }
 
 impl Roads {
+    /// Point nodes still using the old `wheel_entrance` flag at the nearest attraction
+    pub fn resolve_legacy_entrances<'a>(
+        &mut self,
+        attractions: impl IntoIterator<Item = (&'a str, vec2<f32>)>,
+    ) {
+        let attractions: Vec<(&str, vec2<f32>)> = attractions.into_iter().collect();
+        for node in &mut self.nodes {
+            if !std::mem::take(&mut node.wheel_entrance) || node.attraction.is_some() {
+                continue;
+            }
+            node.attraction = attractions
+                .iter()
+                .min_by_key(|&&(_, pos)| r32((pos - node.pos).len()))
+                .map(|&(name, _)| name.to_owned());
+        }
+    }
+
+    /// Edges to missing nodes are skipped, they are reported by validation
+    pub fn update_outgoing(&mut self) {
+        let count = self.nodes.len();
+        self.outgoing = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.connected
+                    .iter()
+                    .copied()
+                    .filter(|&other| other < count)
+                    .collect()
+            })
+            .collect();
+        for (index, node) in self.nodes.iter().enumerate() {
+            for &other in node.two_way.iter().filter(|&&other| other < count) {
+                self.outgoing[index].push(other);
+                self.outgoing[other].push(index);
+            }
+        }
+        for outgoing in &mut self.outgoing {
+            outgoing.sort();
+            outgoing.dedup();
+        }
+    }
+
+    pub fn outgoing(&self, node: NodeId) -> &[NodeId] {
+        &self.outgoing[node]
+    }
+
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId, two_way: bool) {
+        if from == to {
+            return;
+        }
+        if two_way {
+            self.nodes[from].connected.retain(|&other| other != to);
+            self.nodes[to].connected.retain(|&other| other != from);
+            if !self.nodes[to].two_way.contains(&from) && !self.nodes[from].two_way.contains(&to) {
+                self.nodes[from].two_way.push(to);
+            }
+        } else if !self.nodes[from].connected.contains(&to) {
+            self.nodes[from].connected.push(to);
+        }
+        self.update_outgoing();
+    }
+
+    /// Insert a node on an edge, `from` being the node storing it
+    pub fn split_edge(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        two_way: bool,
+        pos: vec2<f32>,
+    ) -> NodeId {
+        let index = self.nodes.len();
+        let mut node = RoadNode::new(pos);
+        let edges = if two_way {
+            node.two_way.push(to);
+            &mut self.nodes[from].two_way
+        } else {
+            node.connected.push(to);
+            &mut self.nodes[from].connected
+        };
+        for other in edges {
+            if *other == to {
+                *other = index;
+            }
+        }
+        self.nodes.push(node);
+        self.update_outgoing();
+        index
+    }
+
+    /// New index of `node` once `removed` is gone
+    pub fn index_after_removal(removed: NodeId, node: NodeId) -> Option<NodeId> {
+        match node.cmp(&removed) {
+            std::cmp::Ordering::Less => Some(node),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(node - 1),
+        }
+    }
+
+    pub fn remove_node(&mut self, index: NodeId) {
+        self.nodes.remove(index);
+        for node in &mut self.nodes {
+            for edges in [&mut node.connected, &mut node.two_way] {
+                *edges = edges
+                    .iter()
+                    .filter_map(|&to| Self::index_after_removal(index, to))
+                    .collect();
+            }
+        }
+        self.update_outgoing();
+    }
+
+    /// Moves the edges and flags of `removed` over to `keep`, then removes it
+    pub fn merge_nodes(&mut self, keep: NodeId, removed: NodeId) {
+        if keep == removed {
+            return;
+        }
+        for node in &mut self.nodes {
+            for edges in [&mut node.connected, &mut node.two_way] {
+                for to in edges {
+                    if *to == removed {
+                        *to = keep;
+                    }
+                }
+            }
+        }
+        let merged = self.nodes[removed].clone();
+        let node = &mut self.nodes[keep];
+        node.connected.extend(merged.connected);
+        node.two_way.extend(merged.two_way);
+        node.attraction = node.attraction.take().or(merged.attraction);
+        node.entrance |= merged.entrance;
+        node.exit |= merged.exit;
+        node.idle_spot |= merged.idle_spot;
+        for (index, node) in self.nodes.iter_mut().enumerate() {
+            for edges in [&mut node.connected, &mut node.two_way] {
+                edges.retain(|&to| to != index);
+                edges.sort();
+                edges.dedup();
+            }
+        }
+        self.remove_node(removed);
+    }
+
     pub fn world_pos(&self, position: &Position) -> vec2<f32> {
         let from = self.nodes[position.from].pos;
         let to = match position.to {
             Some(to) => self.nodes[to].pos,
             None => return from,
         };
-        from + (to - from).normalize() * position.distance
+        // Nodes can be stacked on each other while being moved in the editor
+        from + (to - from).normalize_or_zero() * position.distance
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationKind {
+    Walk,
+    Idle,
+    Wave,
+    Celebrate,
+}
+
+impl AnimationKind {
+    /// Key in the `clips` table of sprite sheet configs
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Walk => "walk",
+            Self::Idle => "idle",
+            Self::Wave => "wave",
+            Self::Celebrate => "celebrate",
+        }
+    }
+}
+
+fn default_clip_speed() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+pub struct AnimationClip {
+    pub row: usize,
+    pub frames: usize,
+    /// Frames per unit of crab animation time
+    #[serde(default = "default_clip_speed")]
+    pub speed: f32,
+}
+
+#[derive(Deserialize)]
+pub struct SpriteSheetConfig {
+    pub texture: String,
+    pub frame_size: vec2<usize>,
+    pub clips: HashMap<AnimationKind, AnimationClip>,
+}
+
+impl SpriteSheetConfig {
+    fn frame_uv(
+        &self,
+        texture_size: vec2<usize>,
+        clip: &AnimationClip,
+        animation_time: f32,
+    ) -> Aabb2<f32> {
+        let frame = (animation_time * clip.speed).max(0.0) as usize % clip.frames.max(1);
+        let texture_size = texture_size.map(|x| x as f32);
+        let frame_size = self.frame_size.map(|x| x as f32) / texture_size;
+        let bottom_left = vec2(
+            frame as f32 * frame_size.x,
+            1.0 - (clip.row + 1) as f32 * frame_size.y,
+        );
+        Aabb2::point(bottom_left).extend_positive(frame_size)
     }
 }
 
+fn default_crab_scale() -> f32 {
+    1.0
+}
+
 #[derive(Deserialize)]
 pub struct CrabConfig {
     pub spawn_weight: f64,
+    pub speed: Option<f32>,
+    #[serde(default = "default_crab_scale")]
+    pub scale: f32,
+    pub jump_height: Option<f32>,
+    pub jump_rotation_amplitude: Option<f32>,
+    pub animation_speed: Option<f32>,
+    pub left_hand_pos: Option<vec2<f32>>,
+    pub right_hand_pos: Option<vec2<f32>>,
+    pub tint: Option<Rgba<f32>>,
+    /// Overrides crab_speed_variation from the global config
+    pub speed_variation: Option<f32>,
+    /// Overrides crab_scale_variation from the global config
+    pub scale_variation: Option<f32>,
+    /// Procedural hopping is used if not specified
+    pub sprite_sheet: Option<SpriteSheetConfig>,
+}
+
+impl CrabConfig {
+    fn speed(&self, config: &Config) -> f32 {
+        self.speed.unwrap_or(config.crab_speed)
+    }
+
+    fn jump_height(&self, config: &Config) -> f32 {
+        self.jump_height.unwrap_or(config.jump_height)
+    }
+
+    fn jump_rotation_amplitude(&self, config: &Config) -> f32 {
+        self.jump_rotation_amplitude
+            .unwrap_or(config.jump_rotation_amplitude)
+    }
+
+    fn animation_speed(&self, config: &Config) -> f32 {
+        self.animation_speed.unwrap_or(config.animation_speed)
+    }
+
+    fn left_hand_pos(&self, config: &Config) -> vec2<f32> {
+        self.left_hand_pos.unwrap_or(config.crab_left_hand_pos)
+    }
+
+    fn right_hand_pos(&self, config: &Config) -> vec2<f32> {
+        self.right_hand_pos.unwrap_or(config.crab_right_hand_pos)
+    }
+
+    fn tint(&self) -> Rgba<f32> {
+        self.tint.unwrap_or(Rgba::WHITE)
+    }
+
+    fn speed_variation(&self, config: &Config) -> f32 {
+        self.speed_variation.unwrap_or(config.crab_speed_variation)
+    }
+
+    fn scale_variation(&self, config: &Config) -> f32 {
+        self.scale_variation.unwrap_or(config.crab_scale_variation)
+    }
 }
 
-#[derive(geng::asset::Load)]
 pub struct CrabAssets {
+    /// Directory name, used in error messages
+    pub name: String,
     pub config: Toml<CrabConfig>,
     pub texture: ugli::Texture,
+    pub sprite_sheet: Option<ugli::Texture>,
 }
 
-#[derive(Deserialize)]
-struct WheelConfig {
-    pub pos: vec2<f32>,
-    pub origin: vec2<f32>,
-    pub base_shift: vec2<f32>,
-    pub radius: f32,
-    pub rotate_speed: f32, // TODO Angle
-    pub cabins: usize,
-    pub swing_origin: vec2<f32>,
-    pub swing_freq: f32,
-    pub swing_amplitude: f32,
-    pub crab_pos: vec2<f32>,
-    pub crab_scale: f32,
-}
-
-#[derive(geng::asset::Load)]
-struct WheelAssets {
-    pub config: Toml<WheelConfig>,
-    pub base: ugli::Texture,
-    pub wheel: ugli::Texture,
-    pub cabin: ugli::Texture,
+impl geng::asset::Load for CrabAssets {
+    fn load(manager: &geng::asset::Manager, path: &std::path::Path) -> geng::asset::Future<Self> {
+        let manager = manager.clone();
+        let path = path.to_owned();
+        async move {
+            let config: Toml<CrabConfig> = manager.load(path.join("config.toml")).await?;
+            let texture = manager.load(path.join("texture.png")).await?;
+            let sprite_sheet = match &config.sprite_sheet {
+                Some(sheet) => Some(manager.load(path.join(&sheet.texture)).await?),
+                None => None,
+            };
+            Ok(Self {
+                name: path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+                config,
+                texture,
+                sprite_sheet,
+            })
+        }
+        .boxed_local()
+    }
+    const DEFAULT_EXT: Option<&'static str> = None;
 }
 
 #[derive(geng::asset::Load)]
@@ -124,13 +748,32 @@ struct Assets {
     pub obstacles: ugli::Texture,
     #[load(postprocess = "fix_roads")]
     pub roads: Roads,
-    pub wheel: WheelAssets,
+    #[load(listed_in = "_list.ron")]
+    pub attractions: Vec<attraction::AttractionAssets>,
     #[load(listed_in = "_list.ron")]
     pub items: Vec<ugli::Texture>,
     pub item_positions: ItemPositions,
+    pub lamps: Lamps,
+    /// Soft round sprite drawn for lights at night
+    pub light: ugli::Texture,
+    #[load(path = "shaders/light.glsl")]
+    pub light_program: ugli::Program,
+    pub umbrella: ugli::Texture,
     #[load(path = "font/Pangolin-Regular.ttf")]
     pub font: geng::Font,
     pub to_find_background: ugli::Texture,
+    /// Played when a target item is picked up
+    #[load(path = "sounds/found.wav")]
+    pub found_sound: geng::Sound,
+    pub difficulty: Toml<Difficulties>,
+    pub editor: Toml<EditorConfig>,
+}
+
+#[derive(ugli::Vertex, Clone, Copy)]
+struct LightVertex {
+    a_pos: vec2<f32>,
+    a_vt: vec2<f32>,
+    a_color: Rgba<f32>,
 }
 
 struct Position {
@@ -139,17 +782,95 @@ struct Position {
     distance: f32,
 }
 
+enum CrabBehavior {
+    Walking,
+    Idle {
+        time_left: f32,
+    },
+    Queuing {
+        entrance: NodeId,
+        place: usize,
+    },
+    Riding {
+        attraction: usize,
+        cabin: usize,
+        laps_left: usize,
+    },
+    /// Left the map through an exit, removed at the end of the update
+    Exited,
+}
+
 struct Crab {
     type_index: usize,
     position: Position,
+    /// Edge picked on arrival at a node, kept while waiting for its entry to clear
+    next: Option<NodeId>,
+    behavior: CrabBehavior,
+    /// Cruising speed
+    speed: f32,
+    current_speed: f32,
+    scale: f32,
+    /// Sprites face right, so crabs walking left are mirrored
+    flipped: bool,
+    heading: vec2<f32>,
+    /// Offset to the right of the heading
+    lane: f32,
+    target_lane: f32,
     animation_time: f32,
-    left_hand: Option<usize>,
-    right_hand: Option<usize>,
+    left_hand: Option<Held>,
+    right_hand: Option<Held>,
+}
+
+/// Contents of one crab hand
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Held {
+    Item(ItemType),
+    /// Handed out in rainy rounds, never a target or distractor
+    Umbrella,
+}
+
+impl Held {
+    fn item(self) -> Option<ItemType> {
+        match self {
+            Self::Item(item_type) => Some(item_type),
+            Self::Umbrella => None,
+        }
+    }
+}
+
+impl Crab {
+    fn hand(&self, right: bool) -> Option<Held> {
+        if right {
+            self.right_hand
+        } else {
+            self.left_hand
+        }
+    }
+
+    /// Item in the hand, if it is not empty or holding an umbrella
+    fn item(&self, right: bool) -> Option<ItemType> {
+        self.hand(right).and_then(Held::item)
+    }
+
+    fn hand_mut(&mut self, right: bool) -> &mut Option<Held> {
+        if right {
+            &mut self.right_hand
+        } else {
+            &mut self.left_hand
+        }
+    }
 }
 
 struct Editor {
     drag_from: Option<usize>,
     shown: bool,
+    /// Proposed item slots waiting to be accepted or rejected
+    suggestions: Vec<vec2<f32>>,
+    selection: Vec<NodeId>,
+    snap_to_grid: bool,
+    /// Color nodes by strongly connected component and mark sinks
+    show_components: bool,
+    show_traffic: bool,
 }
 
 type ItemType = usize;
@@ -165,19 +886,58 @@ struct Game {
     framebuffer_size: vec2<f32>,
     camera: geng::Camera2d,
     drag: Drag,
+    base_config: serde_json::Value,
+    /// Shared with the menus, updated on hot reload
+    loaded: Rc<RefCell<Loaded>>,
     config: Config,
-    assets: Assets,
+    difficulty: usize,
+    settings: Settings,
+    assets: Rc<Assets>,
+    roads: Roads,
+    item_positions: ItemPositions,
+    lamps: Lamps,
     crabs: Vec<Crab>,
     editor: Editor,
+    /// Number of times crabs walked each directed edge to its end
+    traffic: HashMap<(NodeId, NodeId), usize>,
     current_time: f32,
     items: Vec<Item>,
     to_find: Vec<ItemType>,
+    attractions: Vec<attraction::Attraction>,
+    celebrating: bool,
+    raining: bool,
+    /// Item type being hinted at, with the seconds left
+    hint: Option<(ItemType, f32)>,
+    hint_cooldown: f32,
+    /// Dust and sparkles
+    world_particles: particles::Particles,
+    /// Rain in screen space, drawn under the night tint
+    rain: particles::Particles,
+    /// Confetti, drawn over everything
+    screen_particles: particles::Particles,
+    round: usize,
+    round_end_timer: Option<f32>,
+    paused: bool,
+    transition: Option<geng::state::Transition>,
+    hot_reload: Option<hot_reload::HotReload>,
 }
 
 impl Game {
-    pub fn new(geng: &Geng, assets: Assets, config: Config) -> Self {
+    pub fn new(
+        geng: &Geng,
+        loaded: &Rc<RefCell<Loaded>>,
+        settings: &Settings,
+        difficulty: usize,
+    ) -> Self {
+        let (base_config, assets) = {
+            let loaded = loaded.borrow();
+            (loaded.base_config.clone(), loaded.assets.clone())
+        };
+        let config = assets
+            .difficulty
+            .config(&base_config, difficulty)
+            .expect("Failed to apply difficulty preset");
         let crabs_count = config.crabs;
-        let free_items = config.free_items;
         let mut result = Self {
             current_time: 0.0,
             geng: geng.clone(),
@@ -189,122 +949,1454 @@ impl Game {
             },
             drag: Drag::None,
             crabs: vec![],
-            to_find: rand::seq::index::sample(
-                &mut thread_rng(),
-                assets.items.len(),
-                config.types_to_find,
-            )
-            .into_iter()
-            .collect(),
+            to_find: vec![],
+            base_config,
+            loaded: loaded.clone(),
             config,
-            assets,
+            difficulty,
+            settings: settings.clone(),
+            assets: assets.clone(),
+            roads: assets.roads.clone(),
+            item_positions: assets.item_positions.clone(),
+            lamps: assets.lamps.clone(),
             editor: Editor {
                 drag_from: None,
                 shown: false,
+                suggestions: vec![],
+                selection: vec![],
+                snap_to_grid: false,
+                show_components: false,
+                show_traffic: false,
             },
+            traffic: default(),
             items: vec![],
+            attractions: assets
+                .attractions
+                .iter()
+                .map(|attraction| attraction::Attraction::new(&attraction.config))
+                .collect(),
+            celebrating: false,
+            raining: false,
+            hint: None,
+            hint_cooldown: 0.0,
+            world_particles: default(),
+            rain: default(),
+            screen_particles: default(),
+            round: 0,
+            round_end_timer: None,
+            paused: false,
+            transition: None,
+            hot_reload: hot_reload::HotReload::new(geng),
         };
         for _ in 0..crabs_count {
             result.spawn_crab();
         }
-        for _ in 0..free_items {
-            result.spawn_item();
-        }
+        result.seat_initial_riders();
+        result.new_round();
         result
     }
 
-    fn hovered_road_node(&self) -> Option<NodeId> {
-        let cursor = self.camera.screen_to_world(
-            self.framebuffer_size,
-            self.geng.window().cursor_position().map(|x| x as f32),
-        );
-        self.assets
-            .roads
-            .nodes
-            .iter()
-            .position(|node| (node.pos - cursor).len() < self.config.road_node_ui_radius)
-    }
-
-    fn clamp_camera(&mut self) {
-        let map_size = self.assets.ground.size().map(|x| x as f32);
-        self.camera.fov = self
-            .camera
-            .fov
-            .min(map_size.y as f32)
-            .min(map_size.x / self.framebuffer_size.aspect());
-
-        let possible_positions = Aabb2::ZERO
-            .extend_symmetric(map_size / 2.0)
-            .extend_symmetric(-vec2(
-                self.camera.fov / 2.0 * self.framebuffer_size.aspect(),
-                self.camera.fov / 2.0,
-            ));
-        self.camera.center = self.camera.center.clamp_aabb(possible_positions);
+    fn new_round(&mut self) {
+        self.round += 1;
+        if self.settings.ramp_up
+            && self.round > 1
+            && (self.round - 1) % self.assets.difficulty.rounds_per_level == 0
+            && self.difficulty + 1 < self.assets.difficulty.presets.len()
+        {
+            self.difficulty += 1;
+            self.config = self
+                .assets
+                .difficulty
+                .config(&self.base_config, self.difficulty)
+                .expect("Failed to apply difficulty preset");
+        }
+        self.roll_round();
     }
 
-    fn spawn_item(&mut self) {
-        if let Some(index) = (0..self.assets.item_positions.len())
-            .filter(|index| !self.items.iter().any(|item| item.pos_index == *index))
-            .choose(&mut thread_rng())
+    /// Pick the items to find and hand out every item for the current round
+    fn roll_round(&mut self) {
+        self.round_end_timer = None;
+        self.celebrating = false;
+        self.hint = None;
+        self.world_particles.clear();
+        self.raining = thread_rng().gen_bool(self.config.rain_probability);
+        self.items.clear();
+        for crab in &mut self.crabs {
+            crab.left_hand = None;
+            crab.right_hand = None;
+            if self.raining && thread_rng().gen_bool(self.config.rain_umbrella_probability) {
+                *crab.hand_mut(thread_rng().gen()) = Some(Held::Umbrella);
+            }
+        }
+        for cabin in self
+            .attractions
+            .iter_mut()
+            .flat_map(|attraction| &mut attraction.cabins)
         {
-            self.items.push(Item {
-                pos_index: index,
-                type_index: thread_rng().gen_range(0..self.assets.items.len()),
-                rot: thread_rng().gen_range(0.0..2.0 * f32::PI),
-            });
+            cabin.item = None;
         }
-    }
+        self.to_find = rand::seq::index::sample(
+            &mut thread_rng(),
+            self.assets.items.len(),
+            self.config.types_to_find,
+        )
+        .into_iter()
+        .collect();
 
-    fn spawn_crab(&mut self) {
-        let indices: Vec<usize> = (0..self.assets.roads.nodes.len())
-            .filter(|index| {
-                !self.assets.roads.nodes[*index].connected.is_empty()
-                    || self.crabs.iter().all(|crab| crab.position.from != *index)
-            })
-            .collect();
-        let from = *indices.choose(&mut thread_rng()).unwrap();
-        let to = self.assets.roads.nodes[from]
-            .connected
-            .choose(&mut thread_rng())
-            .copied();
-        let distance = match to {
-            Some(to) => {
-                assert!(to != from);
-                thread_rng().gen_range(
-                    0.0..(self.assets.roads.nodes[from].pos - self.assets.roads.nodes[to].pos)
-                        .len(),
-                )
+        for item_type in self.to_find.clone() {
+            let count =
+                thread_rng().gen_range(self.config.target_min_count..=self.config.target_max_count);
+            for _ in 0..count {
+                let in_hand = thread_rng().gen_bool(self.config.target_in_hand_probability);
+                let in_cabin =
+                    !in_hand && thread_rng().gen_bool(self.config.target_in_cabin_probability);
+                let placed = (in_hand && self.give_to_random_crab(item_type))
+                    || (in_cabin && self.place_in_random_cabin(item_type))
+                    || self.place_on_random_slot(item_type);
+                if !placed && !in_hand {
+                    self.give_to_random_crab(item_type);
+                }
             }
-            None => 0.0,
-        };
-        let random_item = || thread_rng().gen_range(0..self.assets.items.len());
-        let (left_hand, right_hand) =
-            if thread_rng().gen_bool(self.config.crab_hold_item_probability) {
-                if thread_rng().gen_bool(self.config.crab_hold_double_item_probability) {
-                    (Some(random_item()), Some(random_item()))
-                } else if thread_rng().gen() {
-                    (Some(random_item()), None)
-                } else {
-                    (None, Some(random_item()))
+        }
+        // With every slot, hand and cabin taken a target could end up missing,
+        // which would make it count as found already
+        let mut missing = Vec::new();
+        for item_type in self.to_find.clone() {
+            if self.item_count(item_type) == 0 && !self.force_into_random_hand(item_type) {
+                log::warn!("No room for target item {item_type}, dropping it from the round");
+                missing.push(item_type);
+            }
+        }
+        self.to_find
+            .retain(|item_type| !missing.contains(item_type));
+        if self.to_find.is_empty() {
+            log::warn!("No target item could be placed, rolling the round again");
+            self.round_end_timer = Some(self.config.round_restart_delay);
+        }
+
+        // Slots may not allow every type, so a failed distractor does not mean
+        // others would fail too
+        let mut failed = 0;
+        while self.items.len() < self.config.free_items && failed < self.assets.items.len() {
+            let Some(item_type) = self.random_distractor() else {
+                break;
+            };
+            if !self.place_on_random_slot(item_type) {
+                failed += 1;
+            }
+        }
+        for attraction in 0..self.attractions.len() {
+            for cabin in 0..self.attractions[attraction].cabins.len() {
+                if self.attractions[attraction].cabins[cabin].item.is_none()
+                    && thread_rng().gen_bool(self.config.cabin_item_probability)
+                {
+                    self.attractions[attraction].cabins[cabin].item = self.random_distractor();
                 }
+            }
+        }
+        for crab_index in 0..self.crabs.len() {
+            let crab = &self.crabs[crab_index];
+            if crab.item(false).is_none() && crab.item(true).is_none() {
+                let (left_hand, right_hand) = self.random_hands();
+                let crab = &mut self.crabs[crab_index];
+                crab.left_hand = crab.left_hand.or(left_hand.map(Held::Item));
+                crab.right_hand = crab.right_hand.or(right_hand.map(Held::Item));
+            }
+        }
+    }
+
+    /// Least common non-target item type
+    fn random_distractor(&self) -> Option<ItemType> {
+        let distractors: Vec<(ItemType, usize)> = (0..self.assets.items.len())
+            .filter(|item_type| !self.to_find.contains(item_type))
+            .map(|item_type| (item_type, self.item_count(item_type)))
+            .collect();
+        let min_count = distractors.iter().map(|&(_, count)| count).min()?;
+        distractors
+            .into_iter()
+            .filter(|&(_, count)| count == min_count)
+            .map(|(item_type, _)| item_type)
+            .choose(&mut thread_rng())
+    }
+
+    fn random_hands(&self) -> (Option<ItemType>, Option<ItemType>) {
+        let (left, right) = if thread_rng().gen_bool(self.config.crab_hold_item_probability) {
+            if thread_rng().gen_bool(self.config.crab_hold_double_item_probability) {
+                (true, true)
+            } else if thread_rng().gen() {
+                (true, false)
             } else {
-                (None, None)
+                (false, true)
+            }
+        } else {
+            (false, false)
+        };
+        (
+            left.then(|| self.random_distractor()).flatten(),
+            right.then(|| self.random_distractor()).flatten(),
+        )
+    }
+
+    fn place_on_random_slot(&mut self, item_type: ItemType) -> bool {
+        let Some(pos_index) = self
+            .free_item_slots()
+            .filter(|&index| self.item_positions[index].allows(item_type))
+            .choose(&mut thread_rng())
+        else {
+            return false;
+        };
+        self.items.push(Item {
+            type_index: item_type,
+            pos_index,
+            rot: self.item_positions[pos_index].random_rotation(),
+        });
+        true
+    }
+
+    fn place_in_random_cabin(&mut self, item_type: ItemType) -> bool {
+        let Some(cabin) = self
+            .attractions
+            .iter_mut()
+            .flat_map(|attraction| &mut attraction.cabins)
+            .filter(|cabin| cabin.item.is_none())
+            .choose(&mut thread_rng())
+        else {
+            return false;
+        };
+        cabin.item = Some(item_type);
+        true
+    }
+
+    fn give_to_random_crab(&mut self, item_type: ItemType) -> bool {
+        let Some(crab) = self
+            .crabs
+            .iter_mut()
+            .filter(|crab| {
+                !matches!(crab.behavior, CrabBehavior::Exited)
+                    && (crab.left_hand.is_none() || crab.right_hand.is_none())
+            })
+            .choose(&mut thread_rng())
+        else {
+            return false;
+        };
+        let right = match (crab.left_hand, crab.right_hand) {
+            (None, None) => thread_rng().gen(),
+            (Some(_), _) => true,
+            (None, Some(_)) => false,
+        };
+        *crab.hand_mut(right) = Some(Held::Item(item_type));
+        true
+    }
+
+    /// Give the item to a crab even if every hand is taken,
+    /// swapping out a target that has copies elsewhere
+    fn force_into_random_hand(&mut self, item_type: ItemType) -> bool {
+        let hands: Vec<(usize, bool)> = self
+            .crabs
+            .iter()
+            .enumerate()
+            .filter(|(_, crab)| !matches!(crab.behavior, CrabBehavior::Exited))
+            .flat_map(|(crab_index, crab)| {
+                [
+                    (crab_index, false, crab.left_hand),
+                    (crab_index, true, crab.right_hand),
+                ]
+            })
+            .filter(|&(_, _, held)| {
+                held.is_none_or(|held| held.item().is_some_and(|held| self.item_count(held) > 1))
+            })
+            .map(|(crab_index, right, _)| (crab_index, right))
+            .collect();
+        let Some(&(crab_index, right)) = hands.choose(&mut thread_rng()) else {
+            return false;
+        };
+        *self.crabs[crab_index].hand_mut(right) = Some(Held::Item(item_type));
+        true
+    }
+
+    /// Apply reloaded config and assets, keeping crabs and items where still valid
+    fn reload(&mut self, base_config: serde_json::Value, assets: Assets) {
+        let difficulty = self
+            .difficulty
+            .min(assets.difficulty.presets.len().saturating_sub(1));
+        let config = match assets.difficulty.config(&base_config, difficulty) {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("Failed to reload config: {e}");
+                return;
+            }
+        };
+        let targets_left: Vec<ItemType> = self
+            .to_find
+            .iter()
+            .copied()
+            .filter(|&item_type| self.item_count(item_type) > 0)
+            .collect();
+
+        // Crabs keep their random variation relative to the new base values
+        if assets.crabs.len() == self.assets.crabs.len() {
+            for crab in &mut self.crabs {
+                let old = &self.assets.crabs[crab.type_index].config;
+                let new = &assets.crabs[crab.type_index].config;
+                let speed_ratio = new.speed(&config) / old.speed(&self.config);
+                crab.speed *= speed_ratio;
+                crab.current_speed *= speed_ratio;
+                crab.scale *= new.scale / old.scale;
+            }
+        } else {
+            self.crabs
+                .retain(|crab| crab.type_index < assets.crabs.len());
+        }
+
+        let roads_changed = serde_json::to_value(&assets.roads).ok()
+            != serde_json::to_value(&self.assets.roads).ok();
+        if roads_changed {
+            let respawn = assets.roads.nodes.len() != self.roads.nodes.len();
+            self.roads = assets.roads.clone();
+            if respawn {
+                self.crabs.clear();
+                self.traffic.clear();
+            }
+        }
+        let slots_changed = serde_json::to_value(&assets.item_positions).ok()
+            != serde_json::to_value(&self.assets.item_positions).ok();
+        if slots_changed {
+            self.item_positions = assets.item_positions.clone();
+        }
+        if serde_json::to_value(&assets.lamps).ok() != serde_json::to_value(&self.assets.lamps).ok()
+        {
+            self.lamps = assets.lamps.clone();
+        }
+
+        let item_types = assets.items.len();
+        let slots = self.item_positions.len();
+        self.items
+            .retain(|item| item.type_index < item_types && item.pos_index < slots);
+        self.to_find.retain(|&item_type| item_type < item_types);
+        for crab in &mut self.crabs {
+            for hand in [&mut crab.left_hand, &mut crab.right_hand] {
+                if hand
+                    .and_then(Held::item)
+                    .is_some_and(|item_type| item_type >= item_types)
+                {
+                    *hand = None;
+                }
+            }
+        }
+
+        self.base_config = base_config;
+        self.config = config;
+        self.difficulty = difficulty;
+        self.assets = Rc::new(assets);
+        *self.loaded.borrow_mut() = Loaded {
+            base_config: self.base_config.clone(),
+            assets: self.assets.clone(),
+        };
+        self.attractions.truncate(self.assets.attractions.len());
+        for (index, assets) in self.assets.attractions.iter().enumerate() {
+            match self.attractions.get_mut(index) {
+                Some(attraction) => attraction.resize(&assets.config),
+                None => self
+                    .attractions
+                    .push(attraction::Attraction::new(&assets.config)),
+            }
+        }
+        for cabin in self
+            .attractions
+            .iter_mut()
+            .flat_map(|attraction| &mut attraction.cabins)
+        {
+            if cabin
+                .item
+                .map_or(false, |item_type| item_type >= item_types)
+            {
+                cabin.item = None;
+            }
+        }
+        for crab_index in 0..self.crabs.len() {
+            let stranded = match self.crabs[crab_index].behavior {
+                CrabBehavior::Riding {
+                    attraction, cabin, ..
+                } => self
+                    .attractions
+                    .get(attraction)
+                    .map_or(true, |attraction| cabin >= attraction.cabins.len()),
+                CrabBehavior::Queuing { entrance, .. } => {
+                    self.entrance_attraction(entrance).is_none()
+                }
+                _ => false,
             };
+            if stranded {
+                self.crab_start_walking(crab_index);
+            }
+        }
+        self.compact_queues();
+        let respawned = self.crabs.is_empty();
+        if respawned {
+            for _ in 0..self.config.crabs {
+                self.spawn_crab();
+            }
+            self.seat_initial_riders();
+        }
+        // Targets could have left with the removed crabs and items
+        let targets_lost = targets_left
+            .iter()
+            .any(|&item_type| self.item_count(item_type) == 0);
+        if respawned || targets_lost || self.to_find.is_empty() {
+            self.new_round();
+        }
+    }
+
+    /// Renumber queue places from 0 without gaps after crabs were taken out
+    /// of a queue, since only the crab at place 0 ever boards
+    fn compact_queues(&mut self) {
+        let mut queued: Vec<(NodeId, usize, usize)> = self
+            .crabs
+            .iter()
+            .enumerate()
+            .filter_map(|(crab_index, crab)| match crab.behavior {
+                CrabBehavior::Queuing { entrance, place } => Some((entrance, place, crab_index)),
+                _ => None,
+            })
+            .collect();
+        queued.sort();
+        for queue in queued.chunk_by(|a, b| a.0 == b.0) {
+            for (new_place, &(_, _, crab_index)) in queue.iter().enumerate() {
+                if let CrabBehavior::Queuing { place, .. } = &mut self.crabs[crab_index].behavior {
+                    *place = new_place;
+                }
+            }
+        }
+    }
+
+    const PAUSE_BUTTONS: [&'static str; 2] = ["Resume", "Main menu"];
+
+    /// Corner button that opens the pause menu without a keyboard, in the ui camera
+    /// Whole screen as seen by [menu::ui_camera]
+    fn ui_view(&self) -> Aabb2<f32> {
+        let camera = menu::ui_camera();
+        Aabb2::point(camera.center).extend_symmetric(vec2(
+            camera.fov / 2.0 * self.framebuffer_size.x / self.framebuffer_size.y,
+            camera.fov / 2.0,
+        ))
+    }
+
+    fn pause_button_aabb(&self) -> Aabb2<f32> {
+        Aabb2::point(self.ui_view().max - vec2::splat(0.8)).extend_uniform(0.5)
+    }
+
+    fn pause_button_at(&self, screen_pos: vec2<f32>) -> bool {
+        let pos = menu::ui_camera().screen_to_world(self.framebuffer_size, screen_pos);
+        self.pause_button_aabb().contains(pos)
+    }
+
+    fn draw_pause_button(&self, framebuffer: &mut ugli::Framebuffer) {
+        let camera = menu::ui_camera();
+        let aabb = self.pause_button_aabb();
+        self.geng.draw2d().draw2d(
+            framebuffer,
+            &camera,
+            &draw2d::TexturedQuad::new(aabb, &self.assets.to_find_background),
+        );
+        let bar = vec2(0.08, 0.25);
+        for side in [-1.0, 1.0] {
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &camera,
+                &draw2d::Quad::new(
+                    Aabb2::point(aabb.center() + vec2(side * 0.15, 0.0)).extend_symmetric(bar),
+                    Rgba::BLACK,
+                ),
+            );
+        }
+    }
+
+    fn pause_click(&mut self, screen_pos: vec2<f32>) {
+        match menu::button_at(self.framebuffer_size, screen_pos, Self::PAUSE_BUTTONS.len()) {
+            Some(0) => self.paused = false,
+            Some(1) => self.transition = Some(geng::state::Transition::Pop),
+            _ => {}
+        }
+    }
+
+    fn hovered_road_node(&self) -> Option<NodeId> {
+        let cursor = self.camera.screen_to_world(
+            self.framebuffer_size,
+            self.geng.window().cursor_position().map(|x| x as f32),
+        );
+        self.roads
+            .nodes
+            .iter()
+            .position(|node| (node.pos - cursor).len() < self.config.road_node_ui_radius)
+    }
+
+    fn hovered_item_slot(&self) -> Option<usize> {
+        let cursor = self.camera.screen_to_world(
+            self.framebuffer_size,
+            self.geng.window().cursor_position().map(|x| x as f32),
+        );
+        self.item_positions.iter().position(|slot| {
+            let local = (slot.transform(0.0).inverse() * cursor.extend(1.0)).into_2d();
+            local.len() < self.config.road_node_ui_radius
+        })
+    }
+
+    /// Attraction and cabin under the cursor
+    fn hovered_cabin(&self) -> Option<(usize, usize)> {
+        let cursor = self.camera.screen_to_world(
+            self.framebuffer_size,
+            self.geng.window().cursor_position().map(|x| x as f32),
+        );
+        self.cabin_at(cursor)
+    }
+
+    fn cabin_at(&self, pos: vec2<f32>) -> Option<(usize, usize)> {
+        self.attractions
+            .iter()
+            .zip(&self.assets.attractions)
+            .enumerate()
+            .find_map(|(index, (attraction, assets))| {
+                attraction
+                    .cabin_at(&assets.config, assets.cabin.size().map(|x| x as f32), pos)
+                    .map(|cabin| (index, cabin))
+            })
+    }
+
+    fn hovered_road_edge(&self) -> Option<(NodeId, NodeId, bool)> {
+        let cursor = self.camera.screen_to_world(
+            self.framebuffer_size,
+            self.geng.window().cursor_position().map(|x| x as f32),
+        );
+        placement::road_edges(&self.roads)
+            .into_iter()
+            .map(|(from, to, two_way)| {
+                let distance = placement::distance_to_segment(
+                    cursor,
+                    self.roads.nodes[from].pos,
+                    self.roads.nodes[to].pos,
+                );
+                ((from, to, two_way), distance)
+            })
+            .filter(|&(_, distance)| distance < self.config.road_node_ui_radius)
+            .min_by_key(|&(_, distance)| r32(distance))
+            .map(|(edge, _)| edge)
+    }
+
+    fn snap_to_grid(&self, pos: vec2<f32>) -> vec2<f32> {
+        if !self.editor.snap_to_grid {
+            return pos;
+        }
+        let grid = self.assets.editor.grid_size;
+        (pos / grid).map(f32::round) * grid
+    }
+
+    /// Snap onto a nearby node that is not being moved, or to the grid
+    fn snap_to_nodes(&self, pos: vec2<f32>, moving: &[NodeId]) -> vec2<f32> {
+        (0..self.roads.nodes.len())
+            .filter(|index| !moving.contains(index))
+            .map(|index| self.roads.nodes[index].pos)
+            .find(|&other| (other - pos).len() < self.config.road_node_ui_radius)
+            .unwrap_or_else(|| self.snap_to_grid(pos))
+    }
+
+    fn split_hovered_edge(&mut self) {
+        let Some((from, to, two_way)) = self.hovered_road_edge() else {
+            return;
+        };
+        let cursor = self.camera.screen_to_world(
+            self.framebuffer_size,
+            self.geng.window().cursor_position().map(|x| x as f32),
+        );
+        let (a, b) = (self.roads.nodes[from].pos, self.roads.nodes[to].pos);
+        if a == b {
+            return;
+        }
+        let t = (vec2::dot(cursor - a, b - a) / (b - a).len_sqr()).clamp(0.0, 1.0);
+        let node = self.roads.split_edge(from, to, two_way, a + (b - a) * t);
+        self.editor.selection = vec![node];
+    }
+
+    /// Delete the selected nodes, or the hovered one if nothing is selected
+    fn delete_road_nodes(&mut self) {
+        let mut nodes = std::mem::take(&mut self.editor.selection);
+        if nodes.is_empty() {
+            nodes.extend(self.hovered_road_node());
+        }
+        nodes.sort();
+        for node in nodes.into_iter().rev() {
+            self.roads.remove_node(node);
+            self.remap_crab_nodes(|index| Roads::index_after_removal(node, index));
+        }
+        self.traffic.clear();
+    }
+
+    /// Keeps crabs on the same nodes after the road graph was renumbered.
+    /// Crabs standing on a removed node are dropped, those whose edge
+    /// is gone start over from the node they came from.
+    fn remap_crab_nodes(&mut self, map: impl Fn(NodeId) -> Option<NodeId>) {
+        let roads = &self.roads;
+        self.crabs.retain_mut(|crab| {
+            let Some(from) = map(crab.position.from) else {
+                return false;
+            };
+            let edge_kept = crab.position.to.map_or(true, |to| {
+                map(to).map_or(false, |to| to != from && roads.outgoing(from).contains(&to))
+            });
+            let entrance_kept = match &mut crab.behavior {
+                CrabBehavior::Queuing { entrance, .. } => match map(*entrance) {
+                    Some(index) => {
+                        *entrance = index;
+                        true
+                    }
+                    None => false,
+                },
+                _ => true,
+            };
+            crab.position.from = from;
+            crab.position.to = crab.position.to.and_then(&map);
+            // Picked again on arrival
+            crab.next = None;
+            if !edge_kept || !entrance_kept {
+                crab.position = Position {
+                    from,
+                    to: None,
+                    distance: 0.0,
+                };
+                crab.behavior = CrabBehavior::Idle { time_left: 0.0 };
+            }
+            true
+        });
+        self.compact_queues();
+    }
+
+    /// Returns whether the editor took over this drag
+    fn editor_drag_start(&mut self, screen_pos: vec2<f32>) -> bool {
+        let cursor = self
+            .camera
+            .screen_to_world(self.framebuffer_size, screen_pos);
+        if self.geng.window().is_key_pressed(geng::Key::LShift) {
+            self.drag = Drag::Selecting { from: cursor };
+            return true;
+        }
+        let Some(anchor) = self.hovered_road_node() else {
+            return false;
+        };
+        if !self.editor.selection.contains(&anchor) {
+            self.editor.selection = vec![anchor];
+        }
+        self.drag = Drag::MovingNodes {
+            anchor,
+            offset: self.roads.nodes[anchor].pos - cursor,
+        };
+        true
+    }
+
+    fn editor_drag_move(&mut self, screen_pos: vec2<f32>) {
+        let Drag::MovingNodes { anchor, offset } = self.drag else {
+            return;
+        };
+        let cursor = self
+            .camera
+            .screen_to_world(self.framebuffer_size, screen_pos);
+        let target = self.snap_to_nodes(cursor + offset, &self.editor.selection);
+        let delta = target - self.roads.nodes[anchor].pos;
+        for &node in &self.editor.selection {
+            self.roads.nodes[node].pos += delta;
+        }
+        // Exactly, so that dropping it onto a node merges them
+        self.roads.nodes[anchor].pos = target;
+    }
+
+    fn editor_drag_end(&mut self, screen_pos: vec2<f32>) {
+        if let Drag::MovingNodes { anchor, .. } = self.drag {
+            // Dropped onto another node, which would leave a zero length edge
+            let pos = self.roads.nodes[anchor].pos;
+            if let Some(target) = (0..self.roads.nodes.len())
+                .find(|&index| index != anchor && self.roads.nodes[index].pos == pos)
+            {
+                self.roads.merge_nodes(target, anchor);
+                self.remap_crab_nodes(|index| {
+                    Roads::index_after_removal(anchor, if index == anchor { target } else { index })
+                });
+                self.traffic.clear();
+                self.editor.selection.clear();
+            }
+        }
+        if let Drag::Selecting { from } = self.drag {
+            let cursor = self
+                .camera
+                .screen_to_world(self.framebuffer_size, screen_pos);
+            let aabb = Aabb2::from_corners(from, cursor);
+            self.editor.selection = (0..self.roads.nodes.len())
+                .filter(|&index| aabb.contains(self.roads.nodes[index].pos))
+                .collect();
+        }
+    }
+
+    const INSPECTOR_ROWS: usize = 8;
+
+    fn inspector_aabb(&self) -> Aabb2<f32> {
+        let half_width = 5.5 * self.framebuffer_size.x / self.framebuffer_size.y;
+        Aabb2::point(vec2(-half_width + 0.2, 4.5))
+            .extend_right(4.0)
+            .extend_down(Self::INSPECTOR_ROWS as f32 * 0.5 + 0.2)
+    }
+
+    fn inspector_row_aabb(&self, row: usize) -> Aabb2<f32> {
+        let panel = self.inspector_aabb();
+        Aabb2::point(vec2(
+            panel.center().x,
+            panel.max.y - 0.35 - row as f32 * 0.5,
+        ))
+        .extend_symmetric(vec2(panel.width() / 2.0 - 0.1, 0.25))
+    }
+
+    fn cursor_over_inspector(&self) -> bool {
+        let cursor = menu::ui_camera().screen_to_world(
+            self.framebuffer_size,
+            self.geng.window().cursor_position().map(|x| x as f32),
+        );
+        self.inspector_aabb().contains(cursor)
+    }
+
+    /// The hovered node, or the selection if nothing is hovered
+    fn inspected_road_nodes(&self) -> Vec<NodeId> {
+        let hovered = if self.cursor_over_inspector() {
+            None
+        } else {
+            self.hovered_road_node()
+        };
+        match hovered {
+            Some(node) => vec![node],
+            None => self.editor.selection.clone(),
+        }
+    }
+
+    /// Set the flag on every inspected node, or clear it if all of them have it
+    fn toggle_node_flag(&mut self, flag: NodeFlag) {
+        let nodes = self.inspected_road_nodes();
+        let value = !nodes.iter().all(|&node| self.roads.nodes[node].flag(flag));
+        for node in nodes {
+            *self.roads.nodes[node].flag_mut(flag) = value;
+        }
+    }
+
+    /// Sets the inspected nodes to lead to the next attraction, then to none
+    fn cycle_node_attraction(&mut self) {
+        let nodes = self.inspected_road_nodes();
+        let Some(&first) = nodes.first() else {
+            return;
+        };
+        let names: Vec<&String> = self
+            .assets
+            .attractions
+            .iter()
+            .map(|attraction| &attraction.name)
+            .collect();
+        let next = match &self.roads.nodes[first].attraction {
+            None => names.first(),
+            Some(current) => names
+                .iter()
+                .position(|&name| name == current)
+                .and_then(|index| names.get(index + 1)),
+        }
+        .map(|&name| name.clone());
+        for node in nodes {
+            self.roads.nodes[node].attraction = next.clone();
+        }
+    }
+
+    /// Text of every inspector row, with what clicking it does
+    fn inspector_rows(&self) -> Vec<(String, Option<InspectorAction>)> {
+        let nodes = self.inspected_road_nodes();
+        let join = |nodes: &[NodeId]| {
+            if nodes.is_empty() {
+                "-".to_owned()
+            } else {
+                nodes
+                    .iter()
+                    .map(|node| node.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        };
+        let mut rows = vec![];
+        match nodes[..] {
+            [] => {
+                let Some(index) = self.hovered_item_slot() else {
+                    return rows;
+                };
+                let slot = &self.item_positions[index];
+                rows.push((format!("Slot {index}"), None));
+                rows.push((
+                    format!("Position {:.0}, {:.0}", slot.pos.x, slot.pos.y),
+                    None,
+                ));
+                rows.push((format!("Scale {:.2}", slot.scale), None));
+                rows.push((
+                    match slot.rotation {
+                        Some([min, max]) => format!("Rotation {min:.0} to {max:.0}"),
+                        None => "Any rotation".to_owned(),
+                    },
+                    None,
+                ));
+                rows.push((format!("Types: {}", join(&slot.allowed_types)), None));
+                return rows;
+            }
+            [index] => {
+                let node = &self.roads.nodes[index];
+                let incoming: Vec<NodeId> = (0..self.roads.nodes.len())
+                    .filter(|&other| self.roads.outgoing(other).contains(&index))
+                    .collect();
+                rows.push((format!("Node {index}"), None));
+                rows.push((
+                    format!("Position {:.0}, {:.0}", node.pos.x, node.pos.y),
+                    None,
+                ));
+                rows.push((format!("Out: {}", join(self.roads.outgoing(index))), None));
+                rows.push((format!("In: {}", join(&incoming)), None));
+            }
+            _ => rows.push((format!("{} nodes", nodes.len()), None)),
+        }
+        for flag in NodeFlag::ALL {
+            let set = nodes
+                .iter()
+                .filter(|&&node| self.roads.nodes[node].flag(flag))
+                .count();
+            let value = if set == nodes.len() {
+                "yes".to_owned()
+            } else if set == 0 {
+                "no".to_owned()
+            } else {
+                format!("{set}/{}", nodes.len())
+            };
+            rows.push((
+                format!("[{:?}] {}: {value}", flag.key(), flag.name()),
+                Some(InspectorAction::Toggle(flag)),
+            ));
+        }
+        let attraction = &self.roads.nodes[nodes[0]].attraction;
+        let value = if nodes
+            .iter()
+            .any(|&node| &self.roads.nodes[node].attraction != attraction)
+        {
+            "mixed"
+        } else {
+            attraction.as_deref().unwrap_or("none")
+        };
+        rows.push((
+            format!("[W] Attraction: {value}"),
+            Some(InspectorAction::CycleAttraction),
+        ));
+        rows
+    }
+
+    /// Returns whether the click landed on the inspector
+    fn inspector_click(&mut self, screen_pos: vec2<f32>) -> bool {
+        let rows = self.inspector_rows();
+        if rows.is_empty() {
+            return false;
+        }
+        let pos = menu::ui_camera().screen_to_world(self.framebuffer_size, screen_pos);
+        if !self.inspector_aabb().contains(pos) {
+            return false;
+        }
+        let clicked = (0..rows.len()).find(|&row| self.inspector_row_aabb(row).contains(pos));
+        match clicked.and_then(|row| rows[row].1) {
+            Some(InspectorAction::Toggle(flag)) => self.toggle_node_flag(flag),
+            Some(InspectorAction::CycleAttraction) => self.cycle_node_attraction(),
+            None => {}
+        }
+        true
+    }
+
+    fn draw_inspector(&self, framebuffer: &mut ugli::Framebuffer) {
+        let rows = self.inspector_rows();
+        if rows.is_empty() {
+            return;
+        }
+        let camera = menu::ui_camera();
+        self.geng.draw2d().draw2d(
+            framebuffer,
+            &camera,
+            &draw2d::Quad::new(self.inspector_aabb(), Rgba::new(0.0, 0.0, 0.0, 0.6)),
+        );
+        for (row, (text, action)) in rows.iter().enumerate() {
+            let aabb = self.inspector_row_aabb(row);
+            if action.is_some() {
+                self.geng.draw2d().draw2d(
+                    framebuffer,
+                    &camera,
+                    &draw2d::Quad::new(aabb, Rgba::new(1.0, 1.0, 1.0, 0.1)),
+                );
+            }
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &camera,
+                &draw2d::Text::unit(&self.assets.font, text, Rgba::WHITE)
+                    .scale_uniform(0.18)
+                    .translate(aabb.center()),
+            );
+        }
+    }
+
+    /// Remove the lamp under the cursor, or add one there
+    fn toggle_lamp(&mut self, cursor: vec2<f32>) {
+        match self
+            .lamps
+            .iter()
+            .position(|&pos| (pos - cursor).len() < self.config.road_node_ui_radius)
+        {
+            Some(index) => {
+                self.lamps.remove(index);
+            }
+            None => {
+                let pos = self.snap_to_grid(cursor);
+                self.lamps.push(pos);
+            }
+        }
+    }
+
+    fn hovered_suggestion(&self) -> Option<usize> {
+        let cursor = self.camera.screen_to_world(
+            self.framebuffer_size,
+            self.geng.window().cursor_position().map(|x| x as f32),
+        );
+        self.editor
+            .suggestions
+            .iter()
+            .position(|&pos| (pos - cursor).len() < self.config.road_node_ui_radius)
+    }
+
+    fn suggest_item_slots(&mut self) {
+        let avoid: Vec<(vec2<f32>, f32)> = self
+            .assets
+            .attractions
+            .iter()
+            .map(|attraction| {
+                (
+                    attraction.config.pos,
+                    attraction.config.extent() + self.assets.editor.suggestion_attraction_margin,
+                )
+            })
+            .collect();
+        self.editor.suggestions = placement::suggest_item_slots(
+            &self.roads,
+            &self.item_positions,
+            self.assets.editor.suggestion_count,
+            self.assets.editor.suggestion_min_distance,
+            self.assets.editor.suggestion_road_offset,
+            &avoid,
+        );
+    }
+
+    /// Accept the hovered suggestion, or all of them if none is hovered
+    fn accept_suggestions(&mut self) {
+        let accepted = match self.hovered_suggestion() {
+            Some(index) => vec![self.editor.suggestions.remove(index)],
+            None => std::mem::take(&mut self.editor.suggestions),
+        };
+        self.item_positions
+            .extend(accepted.into_iter().map(ItemSlot::new));
+    }
+
+    /// Reject the hovered suggestion, or all of them if none is hovered
+    fn reject_suggestions(&mut self) {
+        match self.hovered_suggestion() {
+            Some(index) => {
+                self.editor.suggestions.remove(index);
+            }
+            None => self.editor.suggestions.clear(),
+        }
+    }
+
+    fn clamp_camera(&mut self) {
+        let map_size = self.assets.ground.size().map(|x| x as f32);
+        self.camera.fov = self
+            .camera
+            .fov
+            .min(map_size.y as f32)
+            .min(map_size.x / self.framebuffer_size.aspect());
+
+        let possible_positions = Aabb2::ZERO
+            .extend_symmetric(map_size / 2.0)
+            .extend_symmetric(-vec2(
+                self.camera.fov / 2.0 * self.framebuffer_size.aspect(),
+                self.camera.fov / 2.0,
+            ));
+        self.camera.center = self.camera.center.clamp_aabb(possible_positions);
+    }
+
+    fn free_item_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.item_positions.len())
+            .filter(|index| !self.items.iter().any(|item| item.pos_index == *index))
+    }
+
+    fn spawn_item(&mut self) {
+        if let Some(index) = self.free_item_slots().choose(&mut thread_rng()) {
+            let slot = &self.item_positions[index];
+            if let Some(type_index) = slot.random_type(self.assets.items.len()) {
+                self.items.push(Item {
+                    pos_index: index,
+                    type_index,
+                    rot: slot.random_rotation(),
+                });
+            }
+        }
+    }
+
+    fn spawn_crab(&mut self) {
+        let indices: Vec<usize> = (0..self.roads.nodes.len())
+            .filter(|index| {
+                !self.roads.outgoing(*index).is_empty()
+                    || self.crabs.iter().all(|crab| crab.position.from != *index)
+            })
+            .collect();
+        let from = *indices.choose(&mut thread_rng()).unwrap();
+        let to = self.random_next_node(from, None);
+        let distance = match to {
+            Some(to) => {
+                assert!(to != from);
+                thread_rng()
+                    .gen_range(0.0..(self.roads.nodes[from].pos - self.roads.nodes[to].pos).len())
+            }
+            None => 0.0,
+        };
+        self.add_crab(Position { from, to, distance });
+    }
+
+    fn spawn_crab_at_entrance(&mut self) {
+        let Some(from) = (0..self.roads.nodes.len())
+            .filter(|&index| self.roads.nodes[index].entrance)
+            .choose(&mut thread_rng())
+        else {
+            return;
+        };
+        let to = self.random_next_node(from, None);
+        let index = self.crabs.len();
+        self.add_crab(Position {
+            from,
+            to: None,
+            distance: 0.0,
+        });
+        if let Some(to) = to {
+            if self.edge_entry_free(index, from, to) {
+                self.crabs[index].position.to = Some(to);
+                return;
+            }
+        }
+        self.crabs.pop();
+    }
+
+    fn add_crab(&mut self, position: Position) {
+        let (left_hand, right_hand) = self.random_hands();
+        let mut hands = [left_hand.map(Held::Item), right_hand.map(Held::Item)];
+        if self.raining && thread_rng().gen_bool(self.config.rain_umbrella_probability) {
+            hands[thread_rng().gen_range(0..2)] = Some(Held::Umbrella);
+        }
+        let [left_hand, right_hand] = hands;
+        let type_index = thread_rng().sample(
+            rand::distributions::WeightedIndex::new(
+                self.assets
+                    .crabs
+                    .iter()
+                    .map(|crab| crab.config.spawn_weight),
+            )
+            .unwrap(),
+        );
+        let crab_config = &self.assets.crabs[type_index].config;
+        let half_width = self.config.road_width / 2.0;
+        let lane = thread_rng().gen_range(-half_width..=half_width);
+        let vary = |variation: f32| 1.0 + thread_rng().gen_range(-variation..=variation);
+        let speed =
+            crab_config.speed(&self.config) * vary(crab_config.speed_variation(&self.config));
         self.crabs.push(Crab {
             left_hand,
             right_hand,
-            type_index: thread_rng().sample(
-                rand::distributions::WeightedIndex::new(
-                    self.assets
-                        .crabs
-                        .iter()
-                        .map(|crab| crab.config.spawn_weight),
-                )
-                .unwrap(),
-            ),
-            position: Position { from, to, distance },
+            type_index,
+            position,
+            behavior: CrabBehavior::Walking,
+            speed,
+            current_speed: speed,
+            scale: crab_config.scale * vary(crab_config.scale_variation(&self.config)),
+            flipped: false,
+            heading: vec2(1.0, 0.0),
+            lane,
+            target_lane: lane,
             animation_time: thread_rng().gen(),
+            next: None,
+        });
+    }
+
+    fn seat_initial_riders(&mut self) {
+        for attraction in 0..self.attractions.len() {
+            let entrances: Vec<NodeId> = (0..self.roads.nodes.len())
+                .filter(|&index| self.entrance_attraction(index) == Some(attraction))
+                .collect();
+            let Some(&entrance) = entrances.choose(&mut thread_rng()) else {
+                continue;
+            };
+            let config = &self.assets.attractions[attraction].config;
+            for cabin in 0..config.cabins {
+                if !thread_rng().gen_bool(self.config.crab_ride_probability) {
+                    continue;
+                }
+                if let Some(crab) = self
+                    .crabs
+                    .iter_mut()
+                    .filter(|crab| matches!(crab.behavior, CrabBehavior::Walking))
+                    .choose(&mut thread_rng())
+                {
+                    crab.position = Position {
+                        from: entrance,
+                        to: None,
+                        distance: 0.0,
+                    };
+                    crab.behavior = CrabBehavior::Riding {
+                        attraction,
+                        cabin,
+                        laps_left: config.ride_laps,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Wheel entrances lead to the closest attraction
+    fn entrance_attraction(&self, node: NodeId) -> Option<usize> {
+        let name = self.roads.nodes[node].attraction.as_ref()?;
+        self.assets
+            .attractions
+            .iter()
+            .position(|attraction| &attraction.name == name)
+    }
+
+    /// Avoids turning back on two-way roads unless it is a dead end
+    fn random_next_node(&self, node: NodeId, came_from: Option<NodeId>) -> Option<NodeId> {
+        let outgoing = self.roads.outgoing(node);
+        outgoing
+            .iter()
+            .copied()
+            .filter(|&next| Some(next) != came_from)
+            .choose(&mut thread_rng())
+            .or_else(|| outgoing.first().copied())
+    }
+
+    fn crab_start_walking(&mut self, crab_index: usize) {
+        let from = self.crabs[crab_index].position.from;
+        let to = self.random_next_node(from, None);
+        if let Some(to) = to {
+            if !self.edge_entry_free(crab_index, from, to) {
+                self.crabs[crab_index].behavior = CrabBehavior::Idle { time_left: 0.0 };
+                return;
+            }
+        }
+        let crab = &mut self.crabs[crab_index];
+        crab.behavior = CrabBehavior::Walking;
+        crab.current_speed = 0.0;
+        crab.position = Position {
+            from,
+            to,
+            distance: 0.0,
+        };
+    }
+
+    /// Target items are left on the ground near the exit so the round stays winnable
+    fn crab_try_exit(&mut self, crab_index: usize, exit: NodeId) -> bool {
+        let exit_pos = self.roads.nodes[exit].pos;
+        let crab = &self.crabs[crab_index];
+        let mut used_slots = Vec::new();
+        for item_type in [crab.item(false), crab.item(true)].into_iter().flatten() {
+            if !self.to_find.contains(&item_type) {
+                continue;
+            }
+            let Some(slot) = self
+                .free_item_slots()
+                .filter(|index| !used_slots.contains(index))
+                .filter(|&index| self.item_positions[index].allows(item_type))
+                .min_by_key(|&index| r32((self.item_positions[index].pos - exit_pos).len()))
+            else {
+                return false;
+            };
+            used_slots.push(slot);
+        }
+        let crab = &mut self.crabs[crab_index];
+        let held = [crab.left_hand.take(), crab.right_hand.take()];
+        crab.behavior = CrabBehavior::Exited;
+        crab.position.to = None;
+        let targets = held
+            .into_iter()
+            .flatten()
+            .filter_map(Held::item)
+            .filter(|item_type| self.to_find.contains(item_type));
+        for (item_type, pos_index) in targets.zip(used_slots) {
+            self.items.push(Item {
+                type_index: item_type,
+                pos_index,
+                rot: self.item_positions[pos_index].random_rotation(),
+            });
+        }
+        true
+    }
+
+    fn crab_arrive(&mut self, crab_index: usize, node: NodeId) {
+        // Crabs waiting for the next edge to clear arrive again every frame
+        if self.crabs[crab_index].next.is_none() {
+            let from = self.crabs[crab_index].position.from;
+            *self.traffic.entry((from, node)).or_default() += 1;
+        }
+        let to = match self.crabs[crab_index].next {
+            Some(to) => to,
+            None => match self.crab_decide(crab_index, node) {
+                Some(to) => to,
+                None => return,
+            },
+        };
+        let blocked = !self.edge_entry_free(crab_index, node, to);
+        let came_from = self.crabs[crab_index].position.from;
+        let crab = &mut self.crabs[crab_index];
+        if blocked {
+            // Wait at the end of the current edge until the next one clears up
+            crab.next = Some(to);
+            crab.position.distance =
+                (self.roads.nodes[came_from].pos - self.roads.nodes[node].pos).len();
+            crab.current_speed = 0.0;
+            return;
+        }
+        crab.next = None;
+        crab.position = Position {
+            from: node,
+            to: Some(to),
+            distance: 0.0,
+        };
+    }
+
+    /// Picks what the crab does at the node it has just reached,
+    /// returning the next edge if it keeps walking
+    fn crab_decide(&mut self, crab_index: usize, node: NodeId) -> Option<NodeId> {
+        if self.roads.nodes[node].exit
+            && thread_rng().gen_bool(self.config.crab_exit_probability)
+            && self.crab_try_exit(crab_index, node)
+        {
+            return None;
+        }
+        let queue_len = self
+            .crabs
+            .iter()
+            .filter(|crab| {
+                matches!(crab.behavior, CrabBehavior::Queuing { entrance, .. } if entrance == node)
+            })
+            .count();
+        let max_queue = self.entrance_attraction(node).map_or(0, |attraction| {
+            self.assets.attractions[attraction].config.max_queue
+        });
+        let behavior =
+            if queue_len < max_queue && thread_rng().gen_bool(self.config.crab_ride_probability) {
+                CrabBehavior::Queuing {
+                    entrance: node,
+                    place: queue_len,
+                }
+            } else if thread_rng().gen_bool(if self.roads.nodes[node].idle_spot {
+                self.config.crab_idle_spot_probability
+            } else {
+                self.config.crab_idle_probability
+            }) {
+                CrabBehavior::Idle {
+                    time_left: thread_rng()
+                        .gen_range(self.config.crab_min_idle_time..=self.config.crab_max_idle_time),
+                }
+            } else {
+                let came_from = self.crabs[crab_index].position.from;
+                if let Some(to) = self.random_next_node(node, Some(came_from)) {
+                    return Some(to);
+                }
+                CrabBehavior::Walking
+            };
+        let crab = &mut self.crabs[crab_index];
+        crab.position = Position {
+            from: node,
+            to: None,
+            distance: 0.0,
+        };
+        crab.behavior = behavior;
+        None
+    }
+
+    fn crab_drop_item(&mut self, crab_index: usize) {
+        let crab = &self.crabs[crab_index];
+        let right = match (crab.item(false), crab.item(true)) {
+            (None, None) => return,
+            (Some(_), None) => false,
+            (None, Some(_)) => true,
+            (Some(_), Some(_)) => thread_rng().gen(),
+        };
+        let item_type = crab.item(right).unwrap();
+        let pos = self.crab_pos(crab);
+        let Some(pos_index) = self
+            .free_item_slots()
+            .filter(|&index| {
+                let slot = &self.item_positions[index];
+                slot.allows(item_type) && (slot.pos - pos).len() < self.config.crab_item_reach
+            })
+            .min_by_key(|&index| r32((self.item_positions[index].pos - pos).len()))
+        else {
+            return;
+        };
+        self.items.push(Item {
+            type_index: item_type,
+            pos_index,
+            rot: self.item_positions[pos_index].random_rotation(),
         });
+        *self.crabs[crab_index].hand_mut(right) = None;
+    }
+
+    fn crab_pick_up_item(&mut self, crab_index: usize) {
+        let crab = &self.crabs[crab_index];
+        let right = match (crab.left_hand, crab.right_hand) {
+            (None, None) => thread_rng().gen(),
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (Some(_), Some(_)) => return,
+        };
+        let pos = self.crab_pos(crab);
+        let Some(item_index) = self.items.iter().position(|item| {
+            (self.item_positions[item.pos_index].pos - pos).len() < self.config.crab_item_reach
+        }) else {
+            return;
+        };
+        let item = self.items.remove(item_index);
+        *self.crabs[crab_index].hand_mut(right) = Some(Held::Item(item.type_index));
+    }
+
+    fn crabs_trade_items(&mut self, a: usize, b: usize) {
+        let a_right = thread_rng().gen();
+        let b_right = thread_rng().gen();
+        let a_item = self.crabs[a].hand(a_right);
+        if a_item == Some(Held::Umbrella) || self.crabs[b].hand(b_right) == Some(Held::Umbrella) {
+            // Nobody gives their umbrella away in the rain
+            return;
+        }
+        let b_item = std::mem::replace(self.crabs[b].hand_mut(b_right), a_item);
+        *self.crabs[a].hand_mut(a_right) = b_item;
+    }
+
+    fn cabin_rider(&self, attraction: usize, cabin: usize) -> Option<usize> {
+        self.crabs.iter().position(|crab| {
+            matches!(
+                crab.behavior,
+                CrabBehavior::Riding { attraction: a, cabin: c, .. } if a == attraction && c == cabin
+            )
+        })
+    }
+
+    fn cabin_reached_bottom(&mut self, attraction: usize, cabin: usize) {
+        if let Some(index) = self.cabin_rider(attraction, cabin) {
+            if let CrabBehavior::Riding { laps_left, .. } = &mut self.crabs[index].behavior {
+                if *laps_left > 1 {
+                    *laps_left -= 1;
+                    return;
+                }
+            }
+            self.crab_start_walking(index);
+        }
+        let Some((index, entrance)) =
+            self.crabs
+                .iter()
+                .enumerate()
+                .find_map(|(index, crab)| match crab.behavior {
+                    CrabBehavior::Queuing { entrance, place: 0 }
+                        if self.entrance_attraction(entrance) == Some(attraction) =>
+                    {
+                        Some((index, entrance))
+                    }
+                    _ => None,
+                })
+        else {
+            return;
+        };
+        self.crabs[index].behavior = CrabBehavior::Riding {
+            attraction,
+            cabin,
+            laps_left: self.assets.attractions[attraction].config.ride_laps,
+        };
+        for crab in &mut self.crabs {
+            if let CrabBehavior::Queuing { entrance: e, place } = &mut crab.behavior {
+                if *e == entrance {
+                    *place -= 1;
+                }
+            }
+        }
+    }
+
+    fn crab_pos(&self, crab: &Crab) -> vec2<f32> {
+        let pos = self.roads.world_pos(&crab.position);
+        match crab.behavior {
+            CrabBehavior::Queuing { entrance, place } => match self.entrance_attraction(entrance) {
+                Some(attraction) => {
+                    pos + self.assets.attractions[attraction].config.queue_step * place as f32
+                }
+                None => pos,
+            },
+            _ => pos + self.lane_offset(crab),
+        }
+    }
+
+    fn lane_offset(&self, crab: &Crab) -> vec2<f32> {
+        -crab.heading.rotate_90() * crab.lane
+    }
+
+    fn crab_ahead(&self, crab_index: usize, lane: f32) -> Option<usize> {
+        following::crab_ahead(&self.crabs, crab_index, lane, &self.config)
+    }
+
+    fn crab_leader(&self, crab_index: usize) -> Option<(usize, f32)> {
+        following::crab_leader(&self.crabs, crab_index, &self.config)
+    }
+
+    fn edge_entry_free(&self, crab_index: usize, from: NodeId, to: NodeId) -> bool {
+        let crab = &self.crabs[crab_index];
+        !self.crabs.iter().enumerate().any(|(other_index, other)| {
+            other_index != crab_index
+                && other.position.from == from
+                && other.position.to == Some(to)
+                && other.position.distance < self.config.crab_min_gap
+                && (other.lane - crab.lane).abs() < self.config.collision_check_radius
+        })
+    }
+
+    fn lane_is_free(&self, crab_index: usize, lane: f32) -> bool {
+        let crab = &self.crabs[crab_index];
+        !self.crabs.iter().enumerate().any(|(other_index, other)| {
+            other_index != crab_index
+                && other.position.from == crab.position.from
+                && other.position.to == crab.position.to
+                && (other.position.distance - crab.position.distance).abs()
+                    < self.config.collision_check_distance
+                && (other.lane - lane).abs() < self.config.collision_check_radius
+        })
+    }
+
+    fn update_lane(&mut self, crab_index: usize, delta_time: f32) {
+        let crab = &self.crabs[crab_index];
+        if crab.position.to.is_some() {
+            if let Some(leader) = self.crab_ahead(crab_index, crab.target_lane) {
+                if self.crabs[leader].speed < crab.speed {
+                    let half_width = self.config.road_width / 2.0;
+                    let step = self.config.collision_check_radius;
+                    if let Some(lane) = [crab.target_lane + step, crab.target_lane - step]
+                        .into_iter()
+                        .filter(|lane| lane.abs() <= half_width)
+                        .filter(|&lane| self.lane_is_free(crab_index, lane))
+                        .choose(&mut thread_rng())
+                    {
+                        self.crabs[crab_index].target_lane = lane;
+                    }
+                }
+            }
+        }
+        let crab = &mut self.crabs[crab_index];
+        let max_change = self.config.lane_change_speed * delta_time;
+        crab.lane += (crab.target_lane - crab.lane).clamp(-max_change, max_change);
     }
 
     fn item_count(&self, item_type: ItemType) -> usize {
@@ -317,78 +2409,674 @@ impl Game {
             .crabs
             .iter()
             .flat_map(|crab| [&crab.left_hand, &crab.right_hand])
-            .filter(|hand| **hand == Some(item_type))
+            .filter(|hand| **hand == Some(Held::Item(item_type)))
+            .count();
+        let cabin_items = self
+            .attractions
+            .iter()
+            .flat_map(|attraction| &attraction.cabins)
+            .filter(|cabin| cabin.item == Some(item_type))
             .count();
-        ground_items + crab_items
+        ground_items + crab_items + cabin_items
+    }
+
+    /// Height of the to find panel counts in the ui camera
+    const TO_FIND_Y: f32 = -4.0;
+    /// How far below its count each icon is
+    const TO_FIND_ICON_DROP: f32 = 0.7;
+    const TO_FIND_ICON_RADIUS: f32 = 0.5;
+
+    /// Where the count of the to find entry is drawn
+    fn to_find_pos(&self, index: usize) -> vec2<f32> {
+        let total_width = self.to_find.len() as f32;
+        vec2(-total_width / 2.0 + index as f32 + 0.5, Self::TO_FIND_Y)
+    }
+
+    fn to_find_icon_aabb(&self, index: usize) -> Aabb2<f32> {
+        Aabb2::point(self.to_find_pos(index) - vec2(0.0, Self::TO_FIND_ICON_DROP))
+            .extend_uniform(Self::TO_FIND_ICON_RADIUS)
+    }
+
+    /// Item type of the to find panel entry at the screen position
+    fn to_find_at(&self, screen_pos: vec2<f32>) -> Option<ItemType> {
+        let pos = menu::ui_camera().screen_to_world(self.framebuffer_size, screen_pos);
+        (0..self.to_find.len())
+            .find(|&index| {
+                // The icon and the count above it
+                self.to_find_icon_aabb(index)
+                    .extend_up(Self::TO_FIND_ICON_DROP)
+                    .contains(pos)
+            })
+            .map(|index| self.to_find[index])
+    }
+
+    /// Sparkle over every copy of a target, asked for by clicking its to find entry
+    fn request_hint(&mut self, item_type: ItemType) {
+        if self.hint_cooldown > 0.0 || self.item_count(item_type) == 0 {
+            return;
+        }
+        self.hint = Some((item_type, self.config.hint_duration));
+        self.hint_cooldown = self.config.hint_cooldown;
+    }
+
+    /// World positions of every item of the type, on the ground, in hands or in cabins
+    fn item_instances(&self, item_type: ItemType) -> Vec<vec2<f32>> {
+        let origin = |matrix: mat3<f32>| (matrix * vec3(0.0, 0.0, 1.0)).into_2d();
+        let ground = self
+            .items
+            .iter()
+            .filter(|item| item.type_index == item_type)
+            .map(|item| self.item_positions[item.pos_index].pos);
+        let hands = self.crabs.iter().flat_map(|crab| {
+            let left = (crab.item(false) == Some(item_type))
+                .then(|| origin(self.crab_matrix_left_hand(crab)));
+            let right = (crab.item(true) == Some(item_type))
+                .then(|| origin(self.crab_matrix_right_hand(crab)));
+            left.into_iter().chain(right)
+        });
+        let cabins = self
+            .attractions
+            .iter()
+            .zip(&self.assets.attractions)
+            .flat_map(|(attraction, assets)| {
+                (0..attraction.cabins.len())
+                    .filter(move |&cabin| attraction.cabins[cabin].item == Some(item_type))
+                    .map(move |cabin| origin(attraction.item_transform(&assets.config, cabin)))
+            });
+        ground.chain(hands).chain(cabins).collect()
+    }
+
+    fn update_effects(&mut self, delta_time: f32) {
+        const SPARKLE_RATE: f32 = 30.0;
+
+        self.world_particles.update(delta_time);
+        self.rain.update(delta_time);
+        self.screen_particles.update(delta_time);
+
+        let half_width = 5.5 * self.framebuffer_size.x / self.framebuffer_size.y;
+        if self.raining {
+            for _ in 0..particles::spawn_count(self.config.rain_rate, delta_time) {
+                let mut drop = particles::Particle::new(
+                    vec2(
+                        thread_rng().gen_range(-half_width - 1.0..=half_width + 1.0),
+                        6.0,
+                    ),
+                    vec2(-1.5, -15.0),
+                    0.3,
+                    Rgba::new(0.7, 0.8, 1.0, 0.6),
+                    1.0,
+                );
+                drop.shape = particles::Shape::Streak;
+                self.rain.spawn(drop);
+            }
+        }
+
+        self.hint_cooldown = (self.hint_cooldown - delta_time).max(0.0);
+        if let Some((item_type, time_left)) = self.hint {
+            let time_left = time_left - delta_time;
+            self.hint = (time_left > 0.0).then_some((item_type, time_left));
+            // The closest one to the view, as items move around
+            let closest = self
+                .item_instances(item_type)
+                .into_iter()
+                .min_by_key(|&pos| r32((pos - self.camera.center).len()));
+            if let Some(pos) = closest {
+                let size = self.camera.fov * 0.004;
+                for _ in 0..particles::spawn_count(SPARKLE_RATE, delta_time) {
+                    let offset = vec2(thread_rng().gen_range(-1.0..=1.0), 0.0)
+                        .rotate(thread_rng().gen_range(0.0..2.0 * f32::PI))
+                        * size
+                        * 4.0;
+                    self.world_particles.spawn(particles::Particle::new(
+                        pos + offset,
+                        vec2(0.0, size * 3.0),
+                        size,
+                        Rgba::new(1.0, 1.0, 0.6, 1.0),
+                        0.6,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn spawn_dust(&mut self, pos: vec2<f32>) {
+        for _ in 0..4 {
+            let vel = vec2(
+                thread_rng().gen_range(-1.0..=1.0),
+                thread_rng().gen_range(0.0..=0.5),
+            ) * 40.0;
+            self.world_particles.spawn(particles::Particle::new(
+                pos,
+                vel,
+                thread_rng().gen_range(6.0..=12.0),
+                Rgba::new(0.8, 0.7, 0.5, 0.6),
+                0.5,
+            ));
+        }
+    }
+
+    fn spawn_confetti(&mut self) {
+        const COLORS: [Rgba<f32>; 5] = [
+            Rgba::RED,
+            Rgba::GREEN,
+            Rgba::BLUE,
+            Rgba::YELLOW,
+            Rgba::MAGENTA,
+        ];
+        let half_width = 5.5 * self.framebuffer_size.x / self.framebuffer_size.y;
+        for _ in 0..self.config.confetti_count {
+            let mut piece = particles::Particle::new(
+                vec2(
+                    thread_rng().gen_range(-half_width..=half_width),
+                    thread_rng().gen_range(5.5..=6.5),
+                ),
+                vec2(
+                    thread_rng().gen_range(-1.0..=1.0),
+                    thread_rng().gen_range(-2.0..=0.0),
+                ),
+                0.06,
+                *COLORS.choose(&mut thread_rng()).unwrap(),
+                4.0,
+            );
+            piece.gravity = 2.0;
+            piece.shape = particles::Shape::Square;
+            self.screen_particles.spawn(piece);
+        }
+    }
+
+    fn click(&mut self, pos: vec2<f32>) {
+        if let Some(item_type) = self.to_find_at(pos) {
+            self.request_hint(item_type);
+            return;
+        }
+        let cursor_world = self.camera.screen_to_world(self.framebuffer_size, pos);
+        let trigger_radius = self.config.click_radius;
+        let can_take = |item: Option<ItemType>| -> bool {
+            match item {
+                Some(item) => self.to_find.contains(&item),
+                None => false,
+            }
+        };
+        let mut found = false;
+        // Ground
+        if let Some(item) = self.items.iter().position(|item| {
+            can_take(Some(item.type_index))
+                && (self.item_positions[item.pos_index].pos - cursor_world).len() < trigger_radius
+        }) {
+            self.items.remove(item);
+            found = true;
+        }
+
+        // Cabin
+        if let Some((attraction, cabin)) = self.cabin_at(cursor_world) {
+            let cabin = &mut self.attractions[attraction].cabins[cabin];
+            if can_take(cabin.item) {
+                cabin.item = None;
+                found = true;
+            }
+        }
+
+        // Crab
+        for i in (0..self.crabs.len()).rev() {
+            let crab = &self.crabs[i];
+            let check = |matrix: mat3<f32>| -> bool {
+                let pos = (matrix * vec3(0.0, 0.0, 1.0)).into_2d();
+                (pos - cursor_world).len() < trigger_radius
+            };
+            if check(self.crab_matrix_left_hand(crab)) && can_take(crab.item(false)) {
+                self.crabs[i].left_hand = None;
+                found = true;
+            } else if check(self.crab_matrix_right_hand(crab)) && can_take(crab.item(true)) {
+                self.crabs[i].right_hand = None;
+                found = true;
+            }
+        }
+
+        if found {
+            self.assets.found_sound.play();
+        }
+    }
+
+    fn edit_item_slot(&mut self, index: usize, key: geng::Key) {
+        const ROTATION_STEP: f32 = 15.0;
+        let slot = &mut self.item_positions[index];
+        let type_keys = [
+            geng::Key::Num1,
+            geng::Key::Num2,
+            geng::Key::Num3,
+            geng::Key::Num4,
+            geng::Key::Num5,
+            geng::Key::Num6,
+            geng::Key::Num7,
+            geng::Key::Num8,
+            geng::Key::Num9,
+        ];
+        if let Some(item_type) = type_keys.iter().position(|&type_key| type_key == key) {
+            if item_type < self.assets.items.len() {
+                if let Some(i) = slot.allowed_types.iter().position(|&t| t == item_type) {
+                    slot.allowed_types.remove(i);
+                } else {
+                    slot.allowed_types.push(item_type);
+                    slot.allowed_types.sort();
+                }
+            }
+            return;
+        }
+        match key {
+            geng::Key::Num0 => slot.allowed_types.clear(),
+            geng::Key::PageUp => slot.scale *= 1.1,
+            geng::Key::PageDown => slot.scale /= 1.1,
+            geng::Key::O => {
+                slot.rotation = match slot.rotation {
+                    Some(_) => None,
+                    None => Some([0.0, 0.0]),
+                }
+            }
+            geng::Key::Left | geng::Key::Right => {
+                if let Some([min, max]) = &mut slot.rotation {
+                    let delta = if key == geng::Key::Left {
+                        ROTATION_STEP
+                    } else {
+                        -ROTATION_STEP
+                    };
+                    *min += delta;
+                    *max += delta;
+                }
+            }
+            geng::Key::Up => {
+                if let Some([min, max]) = &mut slot.rotation {
+                    *min -= ROTATION_STEP;
+                    *max += ROTATION_STEP;
+                }
+            }
+            geng::Key::Down => {
+                if let Some([min, max]) = &mut slot.rotation {
+                    if *max - *min >= 2.0 * ROTATION_STEP {
+                        *min += ROTATION_STEP;
+                        *max -= ROTATION_STEP;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn crab_animation(&self, crab: &Crab) -> AnimationKind {
+        if self.celebrating {
+            return AnimationKind::Celebrate;
+        }
+        match crab.behavior {
+            CrabBehavior::Walking if crab.position.to.is_some() => AnimationKind::Walk,
+            CrabBehavior::Riding { .. } => AnimationKind::Wave,
+            _ => AnimationKind::Idle,
+        }
+    }
+
+    fn crab_clip(&self, crab: &Crab) -> Option<&AnimationClip> {
+        let sheet = self.assets.crabs[crab.type_index]
+            .config
+            .sprite_sheet
+            .as_ref()?;
+        sheet
+            .clips
+            .get(&self.crab_animation(crab))
+            .or_else(|| sheet.clips.get(&AnimationKind::Idle))
+    }
+
+    fn crab_matrix(&self, crab: &Crab) -> mat3<f32> {
+        let mirror = if crab.flipped { -1.0 } else { 1.0 };
+        let scale = |scale: f32| mat3::scale(vec2(mirror * scale, scale));
+        if let CrabBehavior::Riding {
+            attraction, cabin, ..
+        } = crab.behavior
+        {
+            let config = &self.assets.attractions[attraction].config;
+            return self.attractions[attraction].cabin_transform(config, cabin)
+                * mat3::translate(config.crab_pos)
+                * scale(config.crab_scale * crab.scale);
+        }
+        let crab_config = &self.assets.crabs[crab.type_index].config;
+        let pos = self.crab_pos(crab);
+        if self.crab_clip(crab).is_some() {
+            return mat3::translate(pos) * scale(crab.scale);
+        }
+        let jump = mat3::translate(
+            pos + vec2(
+                0.0,
+                crab.animation_time.cos().abs() * crab_config.jump_height(&self.config),
+            ),
+        );
+        if crab.position.to.is_some() {
+            jump * mat3::rotate(
+                crab.animation_time.sin() * crab_config.jump_rotation_amplitude(&self.config),
+            ) * scale(crab.scale)
+        } else {
+            jump * scale(crab.scale)
+        }
+    }
+
+    fn crab_matrix_left_hand(&self, crab: &Crab) -> mat3<f32> {
+        self.crab_matrix(crab)
+            * mat3::translate(
+                self.assets.crabs[crab.type_index]
+                    .config
+                    .left_hand_pos(&self.config),
+            )
+    }
+
+    fn crab_matrix_right_hand(&self, crab: &Crab) -> mat3<f32> {
+        self.crab_matrix(crab)
+            * mat3::translate(
+                self.assets.crabs[crab.type_index]
+                    .config
+                    .right_hand_pos(&self.config),
+            )
+    }
+
+    fn draw_sprite(
+        &self,
+        framebuffer: &mut ugli::Framebuffer,
+        texture: &ugli::Texture,
+        transform: mat3<f32>,
+        color: Rgba<f32>,
+    ) {
+        self.geng.draw2d().draw2d(
+            framebuffer,
+            &self.camera,
+            &draw2d::TexturedQuad::colored(
+                Aabb2::point(vec2::ZERO).extend_symmetric(texture.size().map(|x| x as f32) / 2.0),
+                texture,
+                color,
+            )
+            .transform(transform),
+        );
+    }
+
+    fn draw_sprite_frame(
+        &self,
+        framebuffer: &mut ugli::Framebuffer,
+        texture: &ugli::Texture,
+        frame_size: vec2<f32>,
+        uv: Aabb2<f32>,
+        transform: mat3<f32>,
+        color: Rgba<f32>,
+    ) {
+        let quad = Aabb2::point(vec2::ZERO).extend_symmetric(frame_size / 2.0);
+        let vertex = |a_pos: vec2<f32>, a_vt: vec2<f32>| draw2d::TexturedVertex {
+            a_pos,
+            a_color: color,
+            a_vt,
+        };
+        self.geng.draw2d().draw2d(
+            framebuffer,
+            &self.camera,
+            &draw2d::TexturedPolygon::new(
+                vec![
+                    vertex(quad.bottom_left(), uv.bottom_left()),
+                    vertex(quad.bottom_right(), uv.bottom_right()),
+                    vertex(quad.top_right(), uv.top_right()),
+                    vertex(quad.top_left(), uv.top_left()),
+                ],
+                texture,
+            )
+            .transform(transform),
+        );
+    }
+
+    /// Edges colored and widened by how many crabs walked them
+    fn draw_traffic(&self, framebuffer: &mut ugli::Framebuffer) {
+        let edges: Vec<(NodeId, NodeId, usize)> = placement::road_edges(&self.roads)
+            .into_iter()
+            .map(|(from, to, two_way)| {
+                let traffic = |from, to| self.traffic.get(&(from, to)).copied().unwrap_or(0);
+                let mut value = traffic(from, to);
+                if two_way {
+                    value += traffic(to, from);
+                }
+                (from, to, value)
+            })
+            .collect();
+        let max = edges.iter().map(|&(_, _, value)| value).max().unwrap_or(0);
+        if max == 0 {
+            return;
+        }
+        for (from, to, value) in edges {
+            let t = value as f32 / max as f32;
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &self.camera,
+                &draw2d::Segment::new(
+                    self.roads.nodes[from].pos,
+                    self.roads.nodes[to].pos,
+                    self.config.road_node_ui_radius * (0.2 + 1.3 * t),
+                    Rgba::new(t, 0.2, 1.0 - t, 0.8),
+                ),
+            );
+        }
     }
 
-    fn click(&mut self, pos: vec2<f32>) {
-        let cursor_world = self.camera.screen_to_world(self.framebuffer_size, pos);
-        let trigger_radius = self.config.click_radius;
-        let can_take = |item: Option<ItemType>| -> bool {
-            match item {
-                Some(item) => self.to_find.contains(&item),
-                None => false,
+    fn draw_components(&self, framebuffer: &mut ugli::Framebuffer) {
+        const PALETTE: [Rgba<f32>; 6] = [
+            Rgba::GREEN,
+            Rgba::CYAN,
+            Rgba::YELLOW,
+            Rgba::MAGENTA,
+            Rgba::BLUE,
+            Rgba::WHITE,
+        ];
+        let components = connectivity::strongly_connected_components(&self.roads);
+        let sinks = connectivity::sink_components(&self.roads, &components);
+        for (node, &component) in self.roads.nodes.iter().zip(&components) {
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &self.camera,
+                &draw2d::Ellipse::circle(
+                    node.pos,
+                    self.config.road_node_ui_radius * 0.8,
+                    PALETTE[component % PALETTE.len()],
+                ),
+            );
+            // The whole map being one component is what we want, not a sink
+            if sinks[component] && sinks.len() > 1 {
+                self.geng.draw2d().draw2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw2d::Ellipse::circle_with_cut(
+                        node.pos,
+                        self.config.road_node_ui_radius * 0.8,
+                        self.config.road_node_ui_radius,
+                        Rgba::RED,
+                    ),
+                );
             }
-        };
-        // Ground
-        if let Some(item) = self.items.iter().position(|item| {
-            can_take(Some(item.type_index))
-                && (self.assets.item_positions[item.pos_index] - cursor_world).len()
-                    < trigger_radius
-        }) {
-            self.items.remove(item);
         }
+    }
 
-        // Crab
-        for i in (0..self.crabs.len()).rev() {
-            let crab = &self.crabs[i];
-            let check = |matrix: mat3<f32>| -> bool {
-                let pos = (matrix * vec3(0.0, 0.0, 1.0)).into_2d();
-                (pos - cursor_world).len() < trigger_radius
-            };
-            if check(self.crab_matrix_left_hand(crab)) && can_take(crab.left_hand) {
-                self.crabs[i].left_hand = None;
-            } else if check(self.crab_matrix_right_hand(crab)) && can_take(crab.right_hand) {
-                self.crabs[i].right_hand = None;
-            }
+    /// 0 at noon, 1 at midnight, the game starts at noon
+    fn night_amount(&self) -> f32 {
+        if self.config.day_length <= 0.0 {
+            return 0.0;
         }
+        let phase = self.current_time / self.config.day_length;
+        let x = (1.0 - (2.0 * f32::PI * phase).cos()) / 2.0;
+        // Smoothstep keeps days and nights longer than the transitions
+        x * x * (3.0 - 2.0 * x)
     }
 
-    fn crab_matrix(&self, crab: &Crab) -> mat3<f32> {
-        let pos = self.assets.roads.world_pos(&crab.position);
-        if crab.position.to.is_some() {
-            mat3::translate(
-                pos + vec2(
-                    0.0,
-                    crab.animation_time.cos().abs() * self.config.jump_height,
-                ),
-            ) * mat3::rotate(crab.animation_time.sin() * self.config.jump_rotation_amplitude)
-        } else {
-            mat3::translate(
-                pos + vec2(
-                    0.0,
-                    crab.animation_time.cos().abs() * self.config.jump_height,
-                ),
-            )
+    /// Darkens everything drawn so far and adds light around lamps and cabins
+    fn draw_lighting(&self, framebuffer: &mut ugli::Framebuffer) {
+        let night = self.night_amount();
+        if night <= 0.0 {
+            return;
         }
+        let (dusk, dark) = (self.config.dusk_color, self.config.night_color);
+        let mut tint = Rgba::new(
+            dusk.r + (dark.r - dusk.r) * night,
+            dusk.g + (dark.g - dusk.g) * night,
+            dusk.b + (dark.b - dusk.b) * night,
+            1.0,
+        );
+        tint.a = self.config.night_darkness * night;
+        self.geng.draw2d().draw2d(
+            framebuffer,
+            &menu::ui_camera(),
+            &draw2d::Quad::new(self.ui_view(), tint),
+        );
+
+        let mut light_color = self.config.light_color;
+        light_color.a *= night;
+        let lamps = self
+            .lamps
+            .iter()
+            .map(|&pos| (pos, self.config.lamp_light_radius));
+        let cabins = self
+            .attractions
+            .iter()
+            .zip(&self.assets.attractions)
+            .flat_map(|(attraction, assets)| {
+                (0..attraction.cabins.len()).map(move |cabin| {
+                    (
+                        attraction.cabin_pos(&assets.config, cabin),
+                        self.config.cabin_light_radius,
+                    )
+                })
+            });
+        let vertices: Vec<LightVertex> = lamps
+            .chain(cabins)
+            .flat_map(|(pos, radius)| {
+                let quad = Aabb2::point(pos).extend_uniform(radius);
+                let corners = [
+                    quad.bottom_left(),
+                    quad.bottom_right(),
+                    quad.top_right(),
+                    quad.top_left(),
+                ];
+                let uvs = [
+                    vec2(0.0, 0.0),
+                    vec2(1.0, 0.0),
+                    vec2(1.0, 1.0),
+                    vec2(0.0, 1.0),
+                ];
+                [0, 1, 2, 0, 2, 3].map(|corner| LightVertex {
+                    a_pos: corners[corner],
+                    a_vt: uvs[corner],
+                    a_color: light_color,
+                })
+            })
+            .collect();
+        // Added on top of the tint, so lights brighten the scene instead of covering it
+        ugli::draw(
+            framebuffer,
+            &self.assets.light_program,
+            ugli::DrawMode::Triangles,
+            &ugli::VertexBuffer::new_dynamic(self.geng.ugli(), vertices),
+            (
+                ugli::uniforms! {
+                    u_texture: &self.assets.light,
+                },
+                self.camera.uniforms(self.framebuffer_size),
+            ),
+            ugli::DrawParameters {
+                blend_mode: Some(ugli::BlendMode::combined(ugli::ChannelBlendMode {
+                    src_factor: ugli::BlendFactor::One,
+                    dst_factor: ugli::BlendFactor::One,
+                    equation: ugli::BlendEquation::Add,
+                })),
+                ..default()
+            },
+        );
     }
 
-    fn crab_matrix_left_hand(&self, crab: &Crab) -> mat3<f32> {
-        self.crab_matrix(crab) * mat3::translate(self.config.crab_left_hand_pos)
+    fn draw_grid(&self, framebuffer: &mut ugli::Framebuffer) {
+        const MAX_LINES: f32 = 200.0;
+        let grid = self.assets.editor.grid_size;
+        let view = Aabb2::point(self.camera.center).extend_symmetric(
+            vec2(
+                self.camera.fov * self.framebuffer_size.aspect(),
+                self.camera.fov,
+            ) / 2.0,
+        );
+        if view.width() / grid > MAX_LINES || view.height() / grid > MAX_LINES {
+            return;
+        }
+        let color = Rgba::new(1.0, 1.0, 1.0, 0.1);
+        let width = self.config.road_node_ui_radius * 0.1;
+        let mut x = (view.min.x / grid).floor() * grid;
+        while x <= view.max.x {
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &self.camera,
+                &draw2d::Segment::new(vec2(x, view.min.y), vec2(x, view.max.y), width, color),
+            );
+            x += grid;
+        }
+        let mut y = (view.min.y / grid).floor() * grid;
+        while y <= view.max.y {
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &self.camera,
+                &draw2d::Segment::new(vec2(view.min.x, y), vec2(view.max.x, y), width, color),
+            );
+            y += grid;
+        }
     }
 
-    fn crab_matrix_right_hand(&self, crab: &Crab) -> mat3<f32> {
-        self.crab_matrix(crab) * mat3::translate(self.config.crab_right_hand_pos)
+    fn draw_crab(&self, framebuffer: &mut ugli::Framebuffer, crab: &Crab) {
+        let crab_assets = &self.assets.crabs[crab.type_index];
+        let matrix = self.crab_matrix(crab);
+        let tint = crab_assets.config.tint();
+        match (
+            &crab_assets.sprite_sheet,
+            &crab_assets.config.sprite_sheet,
+            self.crab_clip(crab),
+        ) {
+            (Some(texture), Some(sheet), Some(clip)) => self.draw_sprite_frame(
+                framebuffer,
+                texture,
+                sheet.frame_size.map(|x| x as f32),
+                sheet.frame_uv(texture.size(), clip, crab.animation_time),
+                matrix,
+                tint,
+            ),
+            _ => self.draw_sprite(framebuffer, &crab_assets.texture, matrix, tint),
+        }
+        for (held, matrix) in [
+            (crab.left_hand, self.crab_matrix_left_hand(crab)),
+            (crab.right_hand, self.crab_matrix_right_hand(crab)),
+        ] {
+            match held {
+                Some(Held::Item(item)) => {
+                    self.draw_sprite(framebuffer, &self.assets.items[item], matrix, Rgba::WHITE)
+                }
+                Some(Held::Umbrella) => self.draw_sprite(
+                    framebuffer,
+                    &self.assets.umbrella,
+                    matrix * mat3::translate(self.config.crab_umbrella_pos),
+                    Rgba::WHITE,
+                ),
+                None => {}
+            }
+        }
     }
 }
 
 impl geng::State for Game {
     fn update(&mut self, delta_time: f64) {
+        if self.paused {
+            return;
+        }
         let delta_time = delta_time as f32;
 
         self.current_time += delta_time;
 
+        if let Some((base_config, assets)) = self
+            .hot_reload
+            .as_mut()
+            .and_then(|hot_reload| hot_reload.update(delta_time))
+        {
+            self.reload(base_config, assets);
+        }
+
         if let Drag::Detecting { from, timer } = &self.drag {
             if timer.elapsed().as_secs_f64() > self.config.drag_start_timer {
                 self.drag = Drag::Dragging {
@@ -397,48 +3085,143 @@ impl geng::State for Game {
             }
         }
 
+        let chance = |rate: f64| thread_rng().gen_bool((rate * delta_time as f64).min(1.0));
+        let speed_multiplier = if self.raining {
+            self.config.rain_crab_speed_multiplier
+        } else {
+            1.0
+        };
         for crab_index in 0..self.crabs.len() {
-            let slow_down = {
-                let mut slow_down = 1.0;
-                let crab = &self.crabs[crab_index];
-                let front_pos = self.assets.roads.world_pos(&Position {
-                    distance: crab.position.distance + self.config.collision_check_distance,
-                    ..crab.position
-                });
-                for other_index in 0..self.crabs.len() {
-                    if other_index == crab_index {
-                        continue;
-                    }
-                    let pos = self
-                        .assets
-                        .roads
-                        .world_pos(&self.crabs[other_index].position);
-                    if (front_pos - pos).len() < self.config.collision_check_radius {
-                        slow_down *= self.config.collision_slow_down;
-                    }
-                }
-                slow_down
-            };
+            let leader = self.crab_leader(crab_index);
+            let collided_with = leader
+                .filter(|&(_, gap)| gap < self.config.crab_desired_gap)
+                .map(|(leader, _)| leader);
+            if matches!(self.crabs[crab_index].behavior, CrabBehavior::Walking) {
+                self.update_lane(crab_index, delta_time);
+            }
             let crab = &mut self.crabs[crab_index];
+            // Hopping crabs touch the ground when the jump cosine crosses zero
+            let landings = |time: f32| ((time - f32::PI / 2.0) / f32::PI).floor();
+            let landed_before = landings(crab.animation_time);
+            crab.animation_time += self.assets.crabs[crab.type_index]
+                .config
+                .animation_speed(&self.config)
+                * delta_time;
+            let hopped = landings(crab.animation_time) != landed_before;
             let position = &mut crab.position;
             if let Some(to) = position.to {
-                position.distance += self.config.crab_speed / slow_down * delta_time;
-                if position.distance
-                    > (self.assets.roads.nodes[position.from].pos - self.assets.roads.nodes[to].pos)
-                        .len()
+                let direction = self.roads.nodes[to].pos.x - self.roads.nodes[position.from].pos.x;
+                if direction != 0.0 {
+                    crab.flipped = direction < 0.0;
+                }
+                crab.heading = (self.roads.nodes[to].pos - self.roads.nodes[position.from].pos)
+                    .normalize_or_zero();
+            }
+            let mut arrived_at = None;
+            let mut done_idling = false;
+            match &mut crab.behavior {
+                CrabBehavior::Walking => {
+                    if let Some(to) = position.to {
+                        crab.current_speed = following::following_speed(
+                            crab.current_speed,
+                            crab.speed * speed_multiplier,
+                            leader.map(|(_, gap)| gap),
+                            &self.config,
+                            delta_time,
+                        );
+                        position.distance += crab.current_speed * delta_time;
+                        if position.distance
+                            > (self.roads.nodes[position.from].pos - self.roads.nodes[to].pos).len()
+                        {
+                            arrived_at = Some(to);
+                        }
+                    }
+                }
+                CrabBehavior::Idle { time_left } => {
+                    *time_left -= delta_time;
+                    done_idling = *time_left <= 0.0;
+                }
+                CrabBehavior::Queuing { .. }
+                | CrabBehavior::Riding { .. }
+                | CrabBehavior::Exited => {}
+            }
+            if let Some(node) = arrived_at {
+                self.crab_arrive(crab_index, node);
+            }
+            if done_idling {
+                self.crab_start_walking(crab_index);
+            }
+
+            let is_walking = |crab: &Crab| {
+                matches!(crab.behavior, CrabBehavior::Walking) && crab.position.to.is_some()
+            };
+            if is_walking(&self.crabs[crab_index]) {
+                if hopped
+                    && self.crab_clip(&self.crabs[crab_index]).is_none()
+                    && thread_rng().gen_bool(self.config.dust_probability)
                 {
-                    *position = Position {
-                        from: to,
-                        to: self.assets.roads.nodes[to]
-                            .connected
-                            .choose(&mut thread_rng())
-                            .copied(),
-                        distance: 0.0,
-                    };
+                    let pos = self.crab_pos(&self.crabs[crab_index]);
+                    self.spawn_dust(pos);
+                }
+                if chance(self.config.crab_drop_item_rate) {
+                    self.crab_drop_item(crab_index);
+                }
+                if chance(self.config.crab_pick_up_item_rate) {
+                    self.crab_pick_up_item(crab_index);
                 }
+                if let Some(other_index) = collided_with {
+                    if is_walking(&self.crabs[other_index])
+                        && chance(self.config.crab_trade_items_rate)
+                    {
+                        self.crabs_trade_items(crab_index, other_index);
+                    }
+                }
+            }
+        }
+
+        self.crabs
+            .retain(|crab| !matches!(crab.behavior, CrabBehavior::Exited));
+        if self.crabs.len() < self.config.crabs && chance(self.config.crab_arrival_rate) {
+            self.spawn_crab_at_entrance();
+        }
+
+        if self.to_find.is_empty() {
+            // Nothing could be placed last time, try again once more crabs are around
+            let timer = self
+                .round_end_timer
+                .get_or_insert(self.config.round_restart_delay);
+            *timer -= delta_time;
+            if *timer <= 0.0 {
+                self.roll_round();
+            }
+        }
+        let was_celebrating = self.celebrating;
+        self.celebrating = !self.to_find.is_empty()
+            && self
+                .to_find
+                .iter()
+                .all(|&item_type| self.item_count(item_type) == 0);
+        if self.celebrating && !was_celebrating {
+            self.spawn_confetti();
+        }
+        if self.celebrating {
+            let timer = self
+                .round_end_timer
+                .get_or_insert(self.config.round_restart_delay);
+            *timer -= delta_time;
+            if *timer <= 0.0 {
+                self.new_round();
             }
+        }
+
+        self.update_effects(delta_time);
 
-            crab.animation_time += self.config.animation_speed * delta_time;
+        for attraction in 0..self.attractions.len() {
+            let arrived = self.attractions[attraction]
+                .update(&self.assets.attractions[attraction].config, delta_time);
+            for cabin in arrived {
+                self.cabin_reached_bottom(attraction, cabin);
+            }
         }
     }
     fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
@@ -447,105 +3230,117 @@ impl geng::State for Game {
 
         self.clamp_camera();
 
-        let mut draw_sprite = |texture: &ugli::Texture, transform: mat3<f32>| {
-            self.geng.draw2d().draw2d(
-                framebuffer,
-                &self.camera,
-                &draw2d::TexturedQuad::new(
-                    Aabb2::point(vec2::ZERO)
-                        .extend_symmetric(texture.size().map(|x| x as f32) / 2.0),
-                    texture,
-                )
-                .transform(transform),
-            );
-        };
-
-        draw_sprite(&self.assets.ground, mat3::identity());
+        self.draw_sprite(
+            framebuffer,
+            &self.assets.ground,
+            mat3::identity(),
+            Rgba::WHITE,
+        );
         let mut crab_indices: Vec<usize> = (0..self.crabs.len()).collect();
         crab_indices
-            .sort_by_key(|index| -r32(self.assets.roads.world_pos(&self.crabs[*index].position).y));
+            .retain(|&index| !matches!(self.crabs[index].behavior, CrabBehavior::Riding { .. }));
+        crab_indices.sort_by_key(|index| -r32(self.crab_pos(&self.crabs[*index]).y));
         for index in crab_indices {
-            let crab = &self.crabs[index];
-            draw_sprite(
-                &self.assets.crabs[crab.type_index].texture,
-                self.crab_matrix(crab),
-            );
-            if let Some(item) = crab.left_hand {
-                draw_sprite(&self.assets.items[item], self.crab_matrix_left_hand(crab));
-            }
-            if let Some(item) = crab.right_hand {
-                draw_sprite(&self.assets.items[item], self.crab_matrix_right_hand(crab));
-            }
+            self.draw_crab(framebuffer, &self.crabs[index]);
         }
-        draw_sprite(&self.assets.obstacles, mat3::identity());
+        self.draw_sprite(
+            framebuffer,
+            &self.assets.obstacles,
+            mat3::identity(),
+            Rgba::WHITE,
+        );
 
         for item in &self.items {
-            draw_sprite(
+            let slot = &self.item_positions[item.pos_index];
+            self.draw_sprite(
+                framebuffer,
                 &self.assets.items[item.type_index],
-                mat3::translate(self.assets.item_positions[item.pos_index])
-                    * mat3::rotate(item.rot),
+                slot.transform(item.rot),
+                Rgba::WHITE,
             );
         }
 
-        // Ferris wheel
-        let wheel_rotation = self.current_time * self.assets.wheel.config.rotate_speed.to_radians();
-        draw_sprite(
-            &self.assets.wheel.base,
-            mat3::translate(self.assets.wheel.config.pos + self.assets.wheel.config.base_shift),
-        );
-        draw_sprite(
-            &self.assets.wheel.wheel,
-            mat3::translate(self.assets.wheel.config.pos)
-                * mat3::rotate(wheel_rotation)
-                * mat3::translate(-self.assets.wheel.config.origin),
-        );
-        for i in 0..self.assets.wheel.config.cabins {
-            let cabin_pos = self.assets.wheel.config.pos
-                + vec2(self.assets.wheel.config.radius, 0.0).rotate(
-                    2.0 * f32::PI * i as f32 / self.assets.wheel.config.cabins as f32
-                        + wheel_rotation,
-                );
-            let cabin_transform = mat3::translate(cabin_pos)
-                * mat3::rotate(
-                    (2.0 * f32::PI * self.current_time * self.assets.wheel.config.swing_freq).sin()
-                        * self.assets.wheel.config.swing_amplitude.to_radians(),
-                )
-                * mat3::translate(-self.assets.wheel.config.swing_origin);
-            draw_sprite(
-                &self.assets.crabs[i % self.assets.crabs.len()].texture,
-                cabin_transform
-                    * mat3::translate(self.assets.wheel.config.crab_pos)
-                    * mat3::scale_uniform(self.assets.wheel.config.crab_scale),
+        for (index, (attraction, assets)) in self
+            .attractions
+            .iter()
+            .zip(&self.assets.attractions)
+            .enumerate()
+        {
+            let config = &assets.config;
+            self.draw_sprite(
+                framebuffer,
+                &assets.base,
+                mat3::translate(config.pos + config.base_shift),
+                Rgba::WHITE,
+            );
+            self.draw_sprite(
+                framebuffer,
+                &assets.rotor,
+                mat3::translate(config.pos)
+                    * mat3::rotate(attraction.rotor_rotation(config))
+                    * mat3::translate(-config.origin),
+                Rgba::WHITE,
             );
-            draw_sprite(&self.assets.wheel.cabin, cabin_transform);
+            for i in attraction.draw_order(config) {
+                if let Some(rider) = self.cabin_rider(index, i) {
+                    self.draw_crab(framebuffer, &self.crabs[rider]);
+                }
+                if let Some(item) = attraction.cabins[i].item {
+                    self.draw_sprite(
+                        framebuffer,
+                        &self.assets.items[item],
+                        attraction.item_transform(config, i),
+                        Rgba::WHITE,
+                    );
+                }
+                self.draw_sprite(
+                    framebuffer,
+                    &assets.cabin,
+                    attraction.cabin_transform(config, i),
+                    Rgba::WHITE,
+                );
+            }
         }
 
-        // Debug wheel
+        self.world_particles
+            .draw(&self.geng, framebuffer, &self.camera);
+        self.rain.draw(&self.geng, framebuffer, &menu::ui_camera());
+        self.draw_lighting(framebuffer);
+
+        // Debug attractions
         if self.editor.shown {
-            for i in 0..self.assets.wheel.config.cabins {
+            let hovered_cabin = self.hovered_cabin();
+            for (index, (attraction, assets)) in self
+                .attractions
+                .iter()
+                .zip(&self.assets.attractions)
+                .enumerate()
+            {
+                for i in 0..attraction.cabins.len() {
+                    self.geng.draw2d().draw2d(
+                        framebuffer,
+                        &self.camera,
+                        &draw2d::Ellipse::circle(
+                            attraction.cabin_pos(&assets.config, i),
+                            self.config.collision_check_radius / 10.0,
+                            if hovered_cabin == Some((index, i)) {
+                                Rgba::YELLOW
+                            } else {
+                                Rgba::RED
+                            },
+                        ),
+                    );
+                }
                 self.geng.draw2d().draw2d(
                     framebuffer,
                     &self.camera,
                     &draw2d::Ellipse::circle(
-                        self.assets.wheel.config.pos
-                            + vec2(self.assets.wheel.config.radius, 0.0).rotate(
-                                2.0 * f32::PI * i as f32 / self.assets.wheel.config.cabins as f32
-                                    + wheel_rotation,
-                            ),
-                        self.config.collision_check_radius / 10.0,
+                        assets.config.pos,
+                        self.config.collision_check_radius,
                         Rgba::RED,
                     ),
                 );
             }
-            self.geng.draw2d().draw2d(
-                framebuffer,
-                &self.camera,
-                &draw2d::Ellipse::circle(
-                    self.assets.wheel.config.pos,
-                    self.config.collision_check_radius,
-                    Rgba::RED,
-                ),
-            );
         }
 
         let ui_camera = geng::Camera2d {
@@ -554,13 +3349,27 @@ impl geng::State for Game {
             fov: 11.0,
         };
 
+        self.geng.draw2d().draw2d(
+            framebuffer,
+            &ui_camera,
+            &draw2d::Text::unit(
+                &self.assets.font,
+                format!(
+                    "Round {} - {}",
+                    self.round, self.assets.difficulty.presets[self.difficulty].name,
+                ),
+                Rgba::WHITE,
+            )
+            .scale_uniform(0.2)
+            .translate(vec2(0.0, 5.0)),
+        );
         if !self.to_find.is_empty() {
             let total_width = self.to_find.len() as f32;
             self.geng.draw2d().draw2d(
                 framebuffer,
                 &ui_camera,
                 &draw2d::TexturedQuad::new(
-                    Aabb2::point(vec2(0.0, -4.0))
+                    Aabb2::point(vec2(0.0, Self::TO_FIND_Y))
                         .extend_symmetric(vec2(total_width / 2.0 + 1.0, 0.0))
                         .extend_up(0.5)
                         .extend_down(1.3),
@@ -569,26 +3378,23 @@ impl geng::State for Game {
             );
             for (i, &item) in self.to_find.iter().enumerate() {
                 let number = self.item_count(item);
-                let pos = vec2(-total_width / 2.0 + i as f32 + 0.5, -4.0);
                 self.geng.draw2d().draw2d(
                     framebuffer,
                     &ui_camera,
                     &draw2d::Text::unit(&self.assets.font, number.to_string(), Rgba::BLACK)
                         .scale_uniform(0.2)
-                        .translate(pos),
+                        .translate(self.to_find_pos(i)),
                 );
                 self.geng.draw2d().draw2d(
                     framebuffer,
                     &ui_camera,
-                    &draw2d::TexturedQuad::unit(&self.assets.items[item])
-                        .scale_uniform(0.5)
-                        .translate(pos + vec2(0.0, -0.7)),
+                    &draw2d::TexturedQuad::new(self.to_find_icon_aabb(i), &self.assets.items[item]),
                 );
             }
         }
 
         // for crab in &self.crabs {
-        //     let pos = self.assets.roads.world_pos(&crab.position);
+        //     let pos = self.roads.world_pos(&crab.position);
         //     self.geng.draw2d().draw2d(
         //         framebuffer,
         //         &self.camera,
@@ -598,18 +3404,71 @@ impl geng::State for Game {
 
         // Road editor
         if self.editor.shown {
-            for position in self.assets.item_positions.iter() {
+            for &pos in &self.editor.suggestions {
+                self.geng.draw2d().draw2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw2d::Ellipse::circle_with_cut(
+                        pos,
+                        self.config.road_node_ui_radius * 0.8,
+                        self.config.road_node_ui_radius,
+                        Rgba::new(0.0, 1.0, 0.0, 0.7),
+                    ),
+                );
+            }
+            for slot in self.item_positions.iter() {
                 self.geng.draw2d().draw2d(
                     framebuffer,
                     &self.camera,
                     &draw2d::Ellipse::circle(
-                        *position,
+                        vec2::ZERO,
                         self.config.road_node_ui_radius,
                         Rgba::BLUE,
-                    ),
+                    )
+                    .transform(slot.transform(0.0)),
                 );
+                if let Some([min, max]) = slot.rotation {
+                    for angle in [min, max] {
+                        // Along the x axis of an item at the rotation limit
+                        let end = (slot.transform(angle.to_radians())
+                            * vec3(self.config.road_node_ui_radius * 2.0, 0.0, 1.0))
+                        .into_2d();
+                        self.geng.draw2d().draw2d(
+                            framebuffer,
+                            &self.camera,
+                            &draw2d::Segment::new_gradient(
+                                draw2d::ColoredVertex {
+                                    a_pos: slot.pos,
+                                    a_color: Rgba::WHITE,
+                                },
+                                draw2d::ColoredVertex {
+                                    a_pos: end,
+                                    a_color: Rgba::WHITE,
+                                },
+                                self.config.road_node_ui_radius * 0.2,
+                            ),
+                        );
+                    }
+                }
+                for (i, &item_type) in slot.allowed_types.iter().enumerate() {
+                    if let Some(texture) = self.assets.items.get(item_type) {
+                        self.geng.draw2d().draw2d(
+                            framebuffer,
+                            &self.camera,
+                            &draw2d::TexturedQuad::unit(texture)
+                                .scale_uniform(self.config.road_node_ui_radius * 0.5)
+                                .translate(
+                                    slot.pos
+                                        + vec2(
+                                            (i as f32 + 1.0) * self.config.road_node_ui_radius,
+                                            self.config.road_node_ui_radius,
+                                        ),
+                                ),
+                        );
+                    }
+                }
             }
-            for node in &self.assets.roads.nodes {
+            for node in &self.roads.nodes {
                 self.geng.draw2d().draw2d(
                     framebuffer,
                     &self.camera,
@@ -620,41 +3479,149 @@ impl geng::State for Game {
                     ),
                 );
             }
-            for from in &self.assets.roads.nodes {
-                for &to in &from.connected {
-                    let to = &self.assets.roads.nodes[to];
+            for from in &self.roads.nodes {
+                let edges = from
+                    .connected
+                    .iter()
+                    .map(|&to| (to, Rgba::BLUE, Rgba::RED))
+                    .chain(
+                        from.two_way
+                            .iter()
+                            .map(|&to| (to, Rgba::MAGENTA, Rgba::MAGENTA)),
+                    );
+                for (to, from_color, to_color) in edges {
+                    let to = &self.roads.nodes[to];
                     self.geng.draw2d().draw2d(
                         framebuffer,
                         &self.camera,
                         &draw2d::Segment::new_gradient(
                             draw2d::ColoredVertex {
                                 a_pos: from.pos,
-                                a_color: Rgba::BLUE,
+                                a_color: from_color,
                             },
                             draw2d::ColoredVertex {
                                 a_pos: to.pos,
-                                a_color: Rgba::RED,
+                                a_color: to_color,
                             },
                             self.config.road_node_ui_radius * 0.5,
                         ),
                     );
                 }
             }
+            for node in &self.roads.nodes {
+                let markers = [
+                    (node.entrance, Rgba::GREEN),
+                    (node.exit, Rgba::RED),
+                    (node.attraction.is_some(), Rgba::YELLOW),
+                    (node.idle_spot, Rgba::new(1.0, 0.5, 0.0, 1.0)),
+                ];
+                for (i, (_, color)) in markers.into_iter().filter(|(shown, _)| *shown).enumerate() {
+                    let radius = self.config.road_node_ui_radius * (1.3 + 0.2 * i as f32);
+                    self.geng.draw2d().draw2d(
+                        framebuffer,
+                        &self.camera,
+                        &draw2d::Ellipse::circle_with_cut(
+                            node.pos,
+                            radius,
+                            radius + self.config.road_node_ui_radius * 0.15,
+                            color,
+                        ),
+                    );
+                }
+            }
             if let Some(index) = self.hovered_road_node() {
                 self.geng.draw2d().draw2d(
                     framebuffer,
                     &self.camera,
                     &draw2d::Ellipse::circle_with_cut(
-                        self.assets.roads.nodes[index].pos,
+                        self.roads.nodes[index].pos,
                         self.config.road_node_ui_radius * 1.1,
                         self.config.road_node_ui_radius * 1.2,
                         Rgba::new(1.0, 1.0, 1.0, 0.5),
                     ),
                 );
             }
+            for &index in &self.editor.selection {
+                self.geng.draw2d().draw2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw2d::Ellipse::circle(
+                        self.roads.nodes[index].pos,
+                        self.config.road_node_ui_radius * 0.6,
+                        Rgba::CYAN,
+                    ),
+                );
+            }
+            if let Drag::Selecting { from } = self.drag {
+                let cursor = self.camera.screen_to_world(
+                    self.framebuffer_size,
+                    self.geng.window().cursor_position().map(|x| x as f32),
+                );
+                self.geng.draw2d().draw2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw2d::Quad::new(
+                        Aabb2::from_corners(from, cursor),
+                        Rgba::new(0.0, 1.0, 1.0, 0.2),
+                    ),
+                );
+            }
+            if self.editor.snap_to_grid {
+                self.draw_grid(framebuffer);
+            }
+            if self.editor.show_traffic {
+                self.draw_traffic(framebuffer);
+            }
+            if self.editor.show_components {
+                self.draw_components(framebuffer);
+            }
+            for &pos in self.lamps.iter() {
+                self.geng.draw2d().draw2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw2d::Ellipse::circle_with_cut(
+                        pos,
+                        self.config.road_node_ui_radius * 0.5,
+                        self.config.road_node_ui_radius,
+                        self.config.light_color,
+                    ),
+                );
+            }
+            self.draw_inspector(framebuffer);
+        }
+
+        self.screen_particles
+            .draw(&self.geng, framebuffer, &menu::ui_camera());
+
+        if self.paused {
+            menu::draw_menu(
+                &self.geng,
+                framebuffer,
+                &self.assets,
+                "Paused",
+                &Self::PAUSE_BUTTONS,
+            );
+        } else {
+            self.draw_pause_button(framebuffer);
         }
     }
     fn handle_event(&mut self, event: geng::Event) {
+        if let geng::Event::KeyDown {
+            key: geng::Key::Escape,
+        } = event
+        {
+            self.paused = !self.paused;
+            self.drag = Drag::None;
+            return;
+        }
+        if self.paused {
+            if let geng::Event::MouseUp { position, .. }
+            | geng::Event::TouchEnd(geng::Touch { position, .. }) = event
+            {
+                self.pause_click(position.map(|x| x as f32));
+            }
+            return;
+        }
         let world_pos = |screen_pos| {
             self.camera
                 .screen_to_world(self.framebuffer_size, screen_pos)
@@ -663,6 +3630,14 @@ impl geng::State for Game {
             geng::Event::MouseDown { position, .. }
             | geng::Event::TouchStart(geng::Touch { position, .. }) => {
                 let pos = position.map(|x| x as f32);
+                if self.pause_button_at(pos) {
+                    self.paused = true;
+                    self.drag = Drag::None;
+                    return;
+                }
+                if self.editor.shown && (self.inspector_click(pos) || self.editor_drag_start(pos)) {
+                    return;
+                }
                 self.drag = Drag::Detecting {
                     from: pos,
                     timer: Timer::new(),
@@ -679,9 +3654,11 @@ impl geng::State for Game {
                     }
                 }
                 if let Drag::Dragging { prev_mouse_pos } = &mut self.drag {
-                    self.camera.center += world_pos(*prev_mouse_pos) - world_pos(pos);
+                    self.camera.center += (world_pos(*prev_mouse_pos) - world_pos(pos))
+                        * self.settings.drag_sensitivity;
                     *prev_mouse_pos = pos;
                 }
+                self.editor_drag_move(pos);
             }
             geng::Event::MouseUp { position, .. }
             | geng::Event::TouchEnd(geng::Touch { position, .. }) => {
@@ -689,13 +3666,18 @@ impl geng::State for Game {
                 if let Drag::Detecting { .. } = self.drag {
                     self.click(pos);
                 }
+                self.editor_drag_end(pos);
                 self.drag = Drag::None;
             }
             geng::Event::Wheel { delta } => {
                 let cursor = self.geng.window().cursor_position().map(|x| x as f32);
                 let prev_world_cursor = world_pos(cursor);
-                self.camera.fov = (self.camera.fov * self.config.zoom_speed.powf(-delta as f32))
-                    .clamp(self.config.min_fov, self.config.max_fov);
+                self.camera.fov = (self.camera.fov
+                    * self
+                        .config
+                        .zoom_speed
+                        .powf(-delta as f32 * self.settings.zoom_sensitivity))
+                .clamp(self.config.min_fov, self.config.max_fov);
                 let new_world_cursor = self.camera.screen_to_world(self.framebuffer_size, cursor);
                 self.camera.center += prev_world_cursor - new_world_cursor;
             }
@@ -704,30 +3686,40 @@ impl geng::State for Game {
                     world_pos(self.geng.window().cursor_position().map(|x| x as f32));
                 match key {
                     geng::Key::Tab => self.editor.shown = !self.editor.shown,
-                    geng::Key::N => self.assets.roads.nodes.push(RoadNode {
-                        pos: cursor_world,
-                        connected: default(),
-                    }),
-                    geng::Key::I => self.assets.item_positions.push(cursor_world),
+                    geng::Key::N => {
+                        let pos = self.snap_to_grid(cursor_world);
+                        self.roads.nodes.push(RoadNode::new(pos));
+                        self.roads.update_outgoing();
+                    }
+                    geng::Key::K => self.split_hovered_edge(),
+                    geng::Key::M => self.editor.snap_to_grid = !self.editor.snap_to_grid,
+                    geng::Key::C => self.editor.show_components = !self.editor.show_components,
+                    geng::Key::H => {
+                        if self.geng.window().is_key_pressed(geng::Key::LShift) {
+                            self.traffic.clear();
+                        } else {
+                            self.editor.show_traffic = !self.editor.show_traffic;
+                        }
+                    }
+                    geng::Key::W => self.cycle_node_attraction(),
+                    geng::Key::P | geng::Key::X | geng::Key::J => {
+                        if let Some(flag) = NodeFlag::ALL.into_iter().find(|flag| flag.key() == key)
+                        {
+                            self.toggle_node_flag(flag);
+                        }
+                    }
+                    geng::Key::I => self.item_positions.push(ItemSlot::new(cursor_world)),
+                    geng::Key::L => self.toggle_lamp(cursor_world),
+                    geng::Key::G => self.suggest_item_slots(),
+                    geng::Key::Enter => self.accept_suggestions(),
+                    geng::Key::Backspace => self.reject_suggestions(),
                     geng::Key::E => {
                         // TODO make engine not send repeated key or smth
                         if self.editor.drag_from.is_none() {
                             self.editor.drag_from = dbg!(self.hovered_road_node());
                         }
                     }
-                    geng::Key::Delete => {
-                        if let Some(index) = self.hovered_road_node() {
-                            self.assets.roads.nodes.remove(index);
-                            for node in &mut self.assets.roads.nodes {
-                                node.connected.retain(|v| *v != index);
-                                for to in &mut node.connected {
-                                    if *to > index {
-                                        *to -= 1;
-                                    }
-                                }
-                            }
-                        }
-                    }
+                    geng::Key::Delete => self.delete_road_nodes(),
                     geng::Key::Space => {
                         if self.geng.window().is_key_pressed(geng::Key::LCtrl) {
                             self.spawn_crab();
@@ -745,7 +3737,7 @@ impl geng::State for Game {
                             std::fs::File::create(run_dir().join("assets").join("roads.json"))
                                 .unwrap(),
                         );
-                        serde_json::to_writer(&mut f, &self.assets.roads).unwrap();
+                        serde_json::to_writer(&mut f, &self.roads).unwrap();
 
                         // save item positions
                         let mut f = std::io::BufWriter::new(
@@ -754,7 +3746,19 @@ impl geng::State for Game {
                             )
                             .unwrap(),
                         );
-                        serde_json::to_writer(&mut f, &self.assets.item_positions).unwrap();
+                        serde_json::to_writer(&mut f, &self.item_positions).unwrap();
+
+                        // save lamps
+                        let mut f = std::io::BufWriter::new(
+                            std::fs::File::create(run_dir().join("assets").join("lamps.json"))
+                                .unwrap(),
+                        );
+                        serde_json::to_writer(&mut f, &self.lamps).unwrap();
+                    }
+                    _ if self.editor.shown && self.hovered_road_node().is_none() => {
+                        if let Some(index) = self.hovered_item_slot() {
+                            self.edit_item_slot(index, key);
+                        }
                     }
                     _ => {}
                 }
@@ -763,7 +3767,8 @@ impl geng::State for Game {
                 geng::Key::E => {
                     if let Some(from) = self.editor.drag_from.take() {
                         if let Some(to) = self.hovered_road_node() {
-                            self.assets.roads.nodes[from].connected.push(to);
+                            let two_way = self.geng.window().is_key_pressed(geng::Key::LShift);
+                            self.roads.add_edge(from, to, two_way);
                         }
                     }
                 }
@@ -772,21 +3777,32 @@ impl geng::State for Game {
             _ => {}
         }
     }
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
 }
 
 fn main() {
     logger::init();
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(command) = <cli::Cli as clap::Parser>::parse().command {
+        if let Err(e) = cli::run(command) {
+            eprintln!("Error: {e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
     geng::setup_panic_handler();
     let geng = Geng::new("Find Ferris");
     geng.clone().run_loading(async move {
-        let config = file::load_detect(run_dir().join("assets").join("config.toml"))
-            .await
-            .unwrap();
-        let assets = geng
-            .asset_manager()
-            .load(run_dir().join("assets"))
-            .await
-            .unwrap();
-        Game::new(&geng, assets, config)
+        let state: Box<dyn geng::State> = match validation::load(geng.clone()).await {
+            Ok((base_config, assets)) => Box::new(menu::MainMenu::new(menu::MenuContext::new(
+                &geng,
+                assets,
+                base_config,
+            ))),
+            Err(errors) => Box::new(menu::ErrorScreen::new(&geng, errors)),
+        };
+        state
     });
 }
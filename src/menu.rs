@@ -0,0 +1,378 @@
+use super::*;
+
+const VOLUME_STEPS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+const SENSITIVITY_STEPS: [f32; 5] = [0.5, 0.75, 1.0, 1.5, 2.0];
+
+pub fn ui_camera() -> geng::Camera2d {
+    geng::Camera2d {
+        center: vec2::ZERO,
+        rotation: 0.0,
+        fov: 11.0,
+    }
+}
+
+/// Vertical list of buttons below the title
+fn button_aabbs(count: usize) -> impl Iterator<Item = Aabb2<f32>> {
+    (0..count)
+        .map(|i| Aabb2::point(vec2(0.0, 2.5 - i as f32 * 1.2)).extend_symmetric(vec2(3.0, 0.5)))
+}
+
+pub fn button_at(
+    framebuffer_size: vec2<f32>,
+    screen_pos: vec2<f32>,
+    count: usize,
+) -> Option<usize> {
+    let pos = ui_camera().screen_to_world(framebuffer_size, screen_pos);
+    button_aabbs(count).position(|aabb| aabb.contains(pos))
+}
+
+pub fn draw_menu(
+    geng: &Geng,
+    framebuffer: &mut ugli::Framebuffer,
+    assets: &Assets,
+    title: &str,
+    labels: &[impl AsRef<str>],
+) {
+    let camera = ui_camera();
+    geng.draw2d().draw2d(
+        framebuffer,
+        &camera,
+        &draw2d::Quad::new(
+            Aabb2::point(vec2::ZERO).extend_uniform(100.0),
+            Rgba::new(0.0, 0.0, 0.0, 0.5),
+        ),
+    );
+    geng.draw2d().draw2d(
+        framebuffer,
+        &camera,
+        &draw2d::Text::unit(&assets.font, title, Rgba::WHITE)
+            .scale_uniform(0.6)
+            .translate(vec2(0.0, 4.0)),
+    );
+    for (label, aabb) in labels.iter().zip(button_aabbs(labels.len())) {
+        geng.draw2d().draw2d(
+            framebuffer,
+            &camera,
+            &draw2d::TexturedQuad::new(aabb, &assets.to_find_background),
+        );
+        geng.draw2d().draw2d(
+            framebuffer,
+            &camera,
+            &draw2d::Text::unit(&assets.font, label.as_ref(), Rgba::BLACK)
+                .scale_uniform(0.3)
+                .translate(aabb.center()),
+        );
+    }
+}
+
+fn next_step<T: PartialOrd + Copy>(steps: &[T], current: T) -> T {
+    steps
+        .iter()
+        .copied()
+        .find(|&step| step > current)
+        .unwrap_or(steps[0])
+}
+
+/// Everything menu screens share
+#[derive(Clone)]
+pub struct MenuContext {
+    geng: Geng,
+    loaded: Rc<RefCell<Loaded>>,
+    settings: Rc<RefCell<Settings>>,
+}
+
+impl MenuContext {
+    pub fn new(geng: &Geng, assets: Assets, base_config: serde_json::Value) -> Self {
+        let mut settings = Settings::load();
+        settings.difficulty = settings
+            .difficulty
+            .min(assets.difficulty.presets.len().saturating_sub(1));
+        geng.audio().set_volume(settings.volume);
+        Self {
+            geng: geng.clone(),
+            loaded: Rc::new(RefCell::new(Loaded {
+                base_config,
+                assets: Rc::new(assets),
+            })),
+            settings: Rc::new(RefCell::new(settings)),
+        }
+    }
+
+    fn start_game(&self, difficulty: usize) -> Box<dyn geng::State> {
+        Box::new(Game::new(
+            &self.geng,
+            &self.loaded,
+            &self.settings.borrow(),
+            difficulty,
+        ))
+    }
+
+    /// Latest assets, including hot reloads done during a game
+    fn assets(&self) -> Rc<Assets> {
+        self.loaded.borrow().assets.clone()
+    }
+
+    fn difficulty_name(&self, difficulty: usize) -> String {
+        self.assets()
+            .difficulty
+            .presets
+            .get(difficulty)
+            .map_or_else(String::new, |preset| preset.name.clone())
+    }
+}
+
+pub struct MainMenu {
+    context: MenuContext,
+    framebuffer_size: vec2<f32>,
+    transition: Option<geng::state::Transition>,
+}
+
+impl MainMenu {
+    pub fn new(context: MenuContext) -> Self {
+        Self {
+            context,
+            framebuffer_size: vec2::splat(1.0),
+            transition: None,
+        }
+    }
+
+    fn labels() -> Vec<&'static str> {
+        let mut labels = vec!["Play", "Difficulty", "Settings"];
+        if cfg!(not(target_arch = "wasm32")) {
+            labels.push("Quit");
+        }
+        labels
+    }
+
+    fn click(&mut self, screen_pos: vec2<f32>) {
+        let state: Box<dyn geng::State> =
+            match button_at(self.framebuffer_size, screen_pos, Self::labels().len()) {
+                Some(0) => {
+                    let difficulty = self.context.settings.borrow().difficulty;
+                    self.context.start_game(difficulty)
+                }
+                Some(1) => Box::new(DifficultySelect::new(self.context.clone())),
+                Some(2) => Box::new(SettingsMenu::new(self.context.clone())),
+                Some(3) => std::process::exit(0),
+                _ => return,
+            };
+        self.transition = Some(geng::state::Transition::Push(state));
+    }
+}
+
+impl geng::State for MainMenu {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        self.framebuffer_size = framebuffer.size().map(|x| x as f32);
+        ugli::clear(framebuffer, Some(Rgba::BLACK), None, None);
+        draw_menu(
+            &self.context.geng,
+            framebuffer,
+            &self.context.assets(),
+            "Find Ferris",
+            &Self::labels(),
+        );
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        if let geng::Event::MouseUp { position, .. }
+        | geng::Event::TouchEnd(geng::Touch { position, .. }) = event
+        {
+            self.click(position.map(|x| x as f32));
+        }
+    }
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+}
+
+/// Start a game with any of the difficulty presets
+pub struct DifficultySelect {
+    context: MenuContext,
+    framebuffer_size: vec2<f32>,
+    transition: Option<geng::state::Transition>,
+}
+
+impl DifficultySelect {
+    pub fn new(context: MenuContext) -> Self {
+        Self {
+            context,
+            framebuffer_size: vec2::splat(1.0),
+            transition: None,
+        }
+    }
+
+    fn labels(&self) -> Vec<String> {
+        let assets = self.context.assets();
+        assets
+            .difficulty
+            .presets
+            .iter()
+            .map(|preset| preset.name.clone())
+            .chain(["Back".to_owned()])
+            .collect()
+    }
+
+    fn click(&mut self, screen_pos: vec2<f32>) {
+        let presets = self.context.assets().difficulty.presets.len();
+        let Some(index) = button_at(self.framebuffer_size, screen_pos, presets + 1) else {
+            return;
+        };
+        self.transition = Some(if index < presets {
+            geng::state::Transition::Switch(self.context.start_game(index))
+        } else {
+            geng::state::Transition::Pop
+        });
+    }
+}
+
+impl geng::State for DifficultySelect {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        self.framebuffer_size = framebuffer.size().map(|x| x as f32);
+        ugli::clear(framebuffer, Some(Rgba::BLACK), None, None);
+        draw_menu(
+            &self.context.geng,
+            framebuffer,
+            &self.context.assets(),
+            "Difficulty",
+            &self.labels(),
+        );
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        if let geng::Event::MouseUp { position, .. }
+        | geng::Event::TouchEnd(geng::Touch { position, .. }) = event
+        {
+            self.click(position.map(|x| x as f32));
+        }
+    }
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+}
+
+/// Each button cycles through the values of one setting
+pub struct SettingsMenu {
+    context: MenuContext,
+    framebuffer_size: vec2<f32>,
+    transition: Option<geng::state::Transition>,
+}
+
+impl SettingsMenu {
+    pub fn new(context: MenuContext) -> Self {
+        Self {
+            context,
+            framebuffer_size: vec2::splat(1.0),
+            transition: None,
+        }
+    }
+
+    fn labels(&self) -> Vec<String> {
+        let settings = self.context.settings.borrow();
+        vec![
+            format!("Volume: {:.0}%", settings.volume * 100.0),
+            format!(
+                "Difficulty: {}",
+                self.context.difficulty_name(settings.difficulty)
+            ),
+            format!("Ramp up: {}", if settings.ramp_up { "on" } else { "off" }),
+            format!("Zoom sensitivity: {}x", settings.zoom_sensitivity),
+            format!("Drag sensitivity: {}x", settings.drag_sensitivity),
+            "Back".to_owned(),
+        ]
+    }
+
+    fn click(&mut self, screen_pos: vec2<f32>) {
+        let Some(index) = button_at(self.framebuffer_size, screen_pos, 6) else {
+            return;
+        };
+        let mut settings = self.context.settings.borrow_mut();
+        match index {
+            0 => {
+                settings.volume = next_step(&VOLUME_STEPS, settings.volume);
+                self.context.geng.audio().set_volume(settings.volume);
+            }
+            1 => {
+                settings.difficulty =
+                    (settings.difficulty + 1) % self.context.assets().difficulty.presets.len();
+            }
+            2 => settings.ramp_up = !settings.ramp_up,
+            3 => {
+                settings.zoom_sensitivity =
+                    next_step(&SENSITIVITY_STEPS, settings.zoom_sensitivity);
+            }
+            4 => {
+                settings.drag_sensitivity =
+                    next_step(&SENSITIVITY_STEPS, settings.drag_sensitivity);
+            }
+            _ => {
+                self.transition = Some(geng::state::Transition::Pop);
+                return;
+            }
+        }
+        settings.save();
+    }
+}
+
+impl geng::State for SettingsMenu {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        self.framebuffer_size = framebuffer.size().map(|x| x as f32);
+        ugli::clear(framebuffer, Some(Rgba::BLACK), None, None);
+        draw_menu(
+            &self.context.geng,
+            framebuffer,
+            &self.context.assets(),
+            "Settings",
+            &self.labels(),
+        );
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        if let geng::Event::MouseUp { position, .. }
+        | geng::Event::TouchEnd(geng::Touch { position, .. }) = event
+        {
+            self.click(position.map(|x| x as f32));
+        }
+    }
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+}
+
+/// Shown instead of the main menu when config or assets are invalid
+pub struct ErrorScreen {
+    geng: Geng,
+    errors: Vec<String>,
+}
+
+impl ErrorScreen {
+    pub fn new(geng: &Geng, errors: Vec<String>) -> Self {
+        for error in &errors {
+            log::error!("{error}");
+        }
+        Self {
+            geng: geng.clone(),
+            errors,
+        }
+    }
+}
+
+impl geng::State for ErrorScreen {
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(framebuffer, Some(Rgba::BLACK), None, None);
+        let camera = ui_camera();
+        let font = self.geng.default_font();
+        self.geng.draw2d().draw2d(
+            framebuffer,
+            &camera,
+            &draw2d::Text::unit(&**font, "Failed to load the game", Rgba::RED)
+                .scale_uniform(0.4)
+                .translate(vec2(0.0, 4.0)),
+        );
+        for (i, error) in self.errors.iter().enumerate() {
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &camera,
+                &draw2d::Text::unit(&**font, error, Rgba::WHITE)
+                    .scale_uniform(0.15)
+                    .translate(vec2(0.0, 3.0 - i as f32 * 0.4)),
+            );
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use super::*;
+
+use std::collections::VecDeque;
+
+/// Past this, new particles replace the earliest ones
+const MAX_PARTICLES: usize = 3000;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Dot,
+    Square,
+    /// Line trailing behind the velocity, for rain
+    Streak,
+}
+
+pub struct Particle {
+    pub pos: vec2<f32>,
+    pub vel: vec2<f32>,
+    /// Downwards acceleration
+    pub gravity: f32,
+    pub size: f32,
+    pub color: Rgba<f32>,
+    /// Seconds left
+    pub life: f32,
+    /// Lifetime at spawn, to fade out
+    pub max_life: f32,
+    pub shape: Shape,
+}
+
+impl Particle {
+    pub fn new(pos: vec2<f32>, vel: vec2<f32>, size: f32, color: Rgba<f32>, life: f32) -> Self {
+        Self {
+            pos,
+            vel,
+            gravity: 0.0,
+            size,
+            color,
+            life,
+            max_life: life,
+            shape: Shape::Dot,
+        }
+    }
+}
+
+/// Particles sharing one camera, world or screen space, oldest first
+#[derive(Default)]
+pub struct Particles {
+    particles: VecDeque<Particle>,
+}
+
+impl Particles {
+    pub fn spawn(&mut self, particle: Particle) {
+        if self.particles.len() >= MAX_PARTICLES {
+            self.particles.pop_front();
+        }
+        self.particles.push_back(particle);
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for particle in &mut self.particles {
+            particle.vel.y -= particle.gravity * delta_time;
+            particle.pos += particle.vel * delta_time;
+            particle.life -= delta_time;
+        }
+        self.particles.retain(|particle| particle.life > 0.0);
+    }
+
+    pub fn draw(&self, geng: &Geng, framebuffer: &mut ugli::Framebuffer, camera: &geng::Camera2d) {
+        for particle in &self.particles {
+            // Fade out during the last third of the life
+            let mut color = particle.color;
+            color.a *= (particle.life / particle.max_life * 3.0).min(1.0);
+            match particle.shape {
+                Shape::Dot => geng.draw2d().draw2d(
+                    framebuffer,
+                    camera,
+                    &draw2d::Ellipse::circle(particle.pos, particle.size, color),
+                ),
+                Shape::Square => geng.draw2d().draw2d(
+                    framebuffer,
+                    camera,
+                    &draw2d::Quad::new(
+                        Aabb2::point(particle.pos).extend_uniform(particle.size),
+                        color,
+                    ),
+                ),
+                Shape::Streak => geng.draw2d().draw2d(
+                    framebuffer,
+                    camera,
+                    &draw2d::Segment::new(
+                        particle.pos,
+                        particle.pos - particle.vel.normalize_or_zero() * particle.size,
+                        particle.size * 0.1,
+                        color,
+                    ),
+                ),
+            }
+        }
+    }
+}
+
+/// Whole number of particles to spawn this frame at `rate` per second
+pub fn spawn_count(rate: f32, delta_time: f32) -> usize {
+    let expected = rate * delta_time;
+    expected.floor() as usize + thread_rng().gen_bool(expected.fract() as f64) as usize
+}
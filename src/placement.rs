@@ -0,0 +1,77 @@
+use super::*;
+
+pub fn distance_to_segment(p: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> f32 {
+    let ab = b - a;
+    let t = if ab.len_sqr() == 0.0 {
+        0.0
+    } else {
+        (vec2::dot(p - a, ab) / ab.len_sqr()).clamp(0.0, 1.0)
+    };
+    (a + ab * t - p).len()
+}
+
+/// Every edge once, with whether it is walkable both ways
+pub fn road_edges(roads: &Roads) -> Vec<(NodeId, NodeId, bool)> {
+    roads
+        .nodes
+        .iter()
+        .enumerate()
+        .flat_map(|(from, node)| {
+            let one_way = node.connected.iter().map(move |&to| (from, to, false));
+            let two_way = node.two_way.iter().map(move |&to| (from, to, true));
+            one_way.chain(two_way)
+        })
+        .collect()
+}
+
+/// Poisson-disk sampling by dart throwing: random points beside the roads,
+/// at least `min_distance` apart from each other and from the existing slots,
+/// and outside of every `avoid` circle
+pub fn suggest_item_slots(
+    roads: &Roads,
+    existing: &[ItemSlot],
+    count: usize,
+    min_distance: f32,
+    road_offset: f32,
+    avoid: &[(vec2<f32>, f32)],
+) -> Vec<vec2<f32>> {
+    const ATTEMPTS_PER_SLOT: usize = 100;
+
+    let segments: Vec<(vec2<f32>, vec2<f32>)> = road_edges(roads)
+        .into_iter()
+        .map(|(from, to, _)| (roads.nodes[from].pos, roads.nodes[to].pos))
+        .filter(|(a, b)| a != b)
+        .collect();
+    let Ok(segment_distribution) =
+        rand::distributions::WeightedIndex::new(segments.iter().map(|&(a, b)| (b - a).len()))
+    else {
+        return vec![];
+    };
+
+    let mut taken: Vec<vec2<f32>> = existing.iter().map(|slot| slot.pos).collect();
+    let mut result = vec![];
+    for _ in 0..count * ATTEMPTS_PER_SLOT {
+        if result.len() >= count {
+            break;
+        }
+        let (a, b) = segments[thread_rng().sample(&segment_distribution)];
+        let side = if thread_rng().gen() { 1.0 } else { -1.0 };
+        let normal = (b - a).normalize().rotate_90();
+        let pos = a + (b - a) * thread_rng().gen::<f32>() + normal * road_offset * side;
+        let too_close_to_road = segments
+            .iter()
+            .any(|&(a, b)| distance_to_segment(pos, a, b) < road_offset * 0.9);
+        let too_close_to_slot = taken
+            .iter()
+            .any(|&other| (other - pos).len() < min_distance);
+        let avoided = avoid
+            .iter()
+            .any(|&(center, radius)| (center - pos).len() < radius);
+        if too_close_to_road || too_close_to_slot || avoided {
+            continue;
+        }
+        taken.push(pos);
+        result.push(pos);
+    }
+    result
+}
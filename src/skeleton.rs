@@ -0,0 +1,433 @@
+use super::*;
+
+/// Walkable pixels of a mask, thinned down to one pixel wide center lines
+struct Skeleton {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+/// Clockwise starting from north, as in the Zhang-Suen paper
+const NEIGHBORS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+impl Skeleton {
+    fn new(mask: &image::GrayAlphaImage, threshold: u8) -> Self {
+        Self {
+            width: mask.width() as usize,
+            height: mask.height() as usize,
+            pixels: mask
+                .pixels()
+                .map(|pixel| pixel[0] >= threshold && pixel[1] >= 128)
+                .collect(),
+        }
+    }
+
+    fn get(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.width
+            && (y as usize) < self.height
+            && self.pixels[y as usize * self.width + x as usize]
+    }
+
+    fn pos(&self, index: usize) -> (i32, i32) {
+        ((index % self.width) as i32, (index / self.width) as i32)
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        y as usize * self.width + x as usize
+    }
+
+    /// Zhang-Suen thinning
+    fn thin(&mut self) {
+        loop {
+            let mut changed = false;
+            for step in 0..2 {
+                let remove: Vec<usize> = (0..self.pixels.len())
+                    .filter(|&index| self.pixels[index])
+                    .filter(|&index| {
+                        let (x, y) = self.pos(index);
+                        let p = NEIGHBORS.map(|(dx, dy)| self.get(x + dx, y + dy));
+                        let count = p.iter().filter(|&&set| set).count();
+                        let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                        let [n, _, e, _, s, _, w, _] = p;
+                        let erodes = if step == 0 {
+                            !(n && e && s) && !(e && s && w)
+                        } else {
+                            !(n && e && w) && !(n && s && w)
+                        };
+                        (2..=6).contains(&count) && transitions == 1 && erodes
+                    })
+                    .collect();
+                changed |= !remove.is_empty();
+                for index in remove {
+                    self.pixels[index] = false;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Mixed adjacency: diagonal neighbors only count if not already
+    /// reachable through a shared orthogonal neighbor, so staircases
+    /// left by thinning do not look like junctions
+    fn neighbors(&self, index: usize) -> Vec<usize> {
+        let (x, y) = self.pos(index);
+        NEIGHBORS
+            .iter()
+            .filter(|&&(dx, dy)| self.get(x + dx, y + dy))
+            .filter(|&&(dx, dy)| {
+                dx == 0 || dy == 0 || !(self.get(x + dx, y) || self.get(x, y + dy))
+            })
+            .map(|&(dx, dy)| self.index(x + dx, y + dy))
+            .collect()
+    }
+}
+
+struct Path {
+    from: usize,
+    to: usize,
+    points: Vec<vec2<f32>>,
+}
+
+impl Path {
+    fn len(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).len())
+            .sum()
+    }
+}
+
+/// Walk the skeleton between junctions and end points, grouping
+/// touching junction pixels into one cluster
+fn trace(
+    skeleton: &Skeleton,
+    to_world: impl Fn(usize) -> vec2<f32>,
+) -> (Vec<vec2<f32>>, Vec<Path>) {
+    let mut cluster_of: HashMap<usize, usize> = HashMap::new();
+    let mut clusters: Vec<Vec<usize>> = vec![];
+    let is_key = |index: usize| skeleton.neighbors(index).len() != 2;
+    let mut key_pixels: Vec<usize> = (0..skeleton.pixels.len())
+        .filter(|&index| skeleton.pixels[index] && is_key(index))
+        .collect();
+
+    let mut visited = vec![false; skeleton.pixels.len()];
+    let mut paths = vec![];
+    let mut traced: HashSet<(usize, usize)> = HashSet::new();
+    let mut next_key = 0;
+    loop {
+        // Cluster the newly found key pixels
+        while next_key < key_pixels.len() {
+            let start = key_pixels[next_key];
+            next_key += 1;
+            if cluster_of.contains_key(&start) {
+                continue;
+            }
+            let cluster = clusters.len();
+            let mut members = vec![];
+            let mut stack = vec![start];
+            while let Some(index) = stack.pop() {
+                if cluster_of.contains_key(&index) {
+                    continue;
+                }
+                cluster_of.insert(index, cluster);
+                visited[index] = true;
+                members.push(index);
+                stack.extend(
+                    skeleton
+                        .neighbors(index)
+                        .into_iter()
+                        .filter(|&other| is_key(other)),
+                );
+            }
+            clusters.push(members);
+        }
+
+        for &start in &key_pixels {
+            for first in skeleton.neighbors(start) {
+                if cluster_of.get(&first) == Some(&cluster_of[&start])
+                    || !traced.insert((start, first))
+                {
+                    continue;
+                }
+                let mut points = vec![to_world(start)];
+                let (mut prev, mut current) = (start, first);
+                while !cluster_of.contains_key(&current) {
+                    visited[current] = true;
+                    points.push(to_world(current));
+                    let Some(next) = skeleton
+                        .neighbors(current)
+                        .into_iter()
+                        .find(|&next| next != prev)
+                    else {
+                        break;
+                    };
+                    (prev, current) = (current, next);
+                }
+                let Some(&to) = cluster_of.get(&current) else {
+                    continue;
+                };
+                traced.insert((current, prev));
+                points.push(to_world(current));
+                paths.push(Path {
+                    from: cluster_of[&start],
+                    to,
+                    points,
+                });
+            }
+        }
+
+        // Closed loops have no junctions, so pick any pixel on them
+        let Some(loop_pixel) =
+            (0..skeleton.pixels.len()).find(|&index| skeleton.pixels[index] && !visited[index])
+        else {
+            break;
+        };
+        key_pixels.push(loop_pixel);
+    }
+
+    let centers = clusters
+        .iter()
+        .map(|members| {
+            members
+                .iter()
+                .map(|&index| to_world(index))
+                .fold(vec2::ZERO, |sum, pos| sum + pos)
+                / members.len() as f32
+        })
+        .collect();
+    (centers, paths)
+}
+
+/// Points along the path splitting it into equal pieces no longer than `spacing`
+fn subdivide(path: &Path, spacing: f32) -> Vec<vec2<f32>> {
+    let len = path.len();
+    let pieces = (len / spacing).ceil().max(1.0) as usize;
+    let mut result = vec![];
+    let mut walked = 0.0;
+    let mut next = len / pieces as f32;
+    for pair in path.points.windows(2) {
+        let segment = (pair[1] - pair[0]).len();
+        while result.len() + 1 < pieces && walked + segment >= next {
+            let t = (next - walked) / segment;
+            result.push(pair[0] + (pair[1] - pair[0]) * t);
+            next += len / pieces as f32;
+        }
+        walked += segment;
+    }
+    result
+}
+
+/// Road graph following the center lines of the walkable areas of the mask.
+/// The mask is stretched over `world_size` centered at the origin like the ground,
+/// branches shorter than `min_branch` leading to dead ends are pruned.
+pub fn roads_from_mask(
+    mask: &image::GrayAlphaImage,
+    threshold: u8,
+    world_size: vec2<f32>,
+    spacing: f32,
+    min_branch: f32,
+) -> Roads {
+    let mut skeleton = Skeleton::new(mask, threshold);
+    skeleton.thin();
+
+    let pixel_size = vec2(
+        world_size.x / skeleton.width as f32,
+        world_size.y / skeleton.height as f32,
+    );
+    let (centers, mut paths) = trace(&skeleton, |index| {
+        let (x, y) = skeleton.pos(index);
+        vec2(
+            (x as f32 + 0.5) * pixel_size.x - world_size.x / 2.0,
+            world_size.y / 2.0 - (y as f32 + 0.5) * pixel_size.y,
+        )
+    });
+
+    let mut degree = vec![0; centers.len()];
+    for path in &paths {
+        degree[path.from] += 1;
+        degree[path.to] += 1;
+    }
+    paths.retain(|path| {
+        let dead_end = degree[path.from] == 1 || degree[path.to] == 1;
+        !(dead_end && path.len() < min_branch)
+    });
+
+    let mut roads = Roads {
+        nodes: vec![],
+        outgoing: vec![],
+    };
+    let mut node_of_cluster: HashMap<usize, NodeId> = HashMap::new();
+    let mut node = |roads: &mut Roads, cluster: usize| {
+        *node_of_cluster.entry(cluster).or_insert_with(|| {
+            roads.nodes.push(RoadNode::new(centers[cluster]));
+            roads.nodes.len() - 1
+        })
+    };
+    for path in &paths {
+        let points = subdivide(path, spacing);
+        if path.from == path.to && points.len() < 2 {
+            // Loop too short to be a road
+            continue;
+        }
+        let from = node(&mut roads, path.from);
+        let to = node(&mut roads, path.to);
+        let mut chain = vec![from];
+        for pos in points {
+            roads.nodes.push(RoadNode::new(pos));
+            chain.push(roads.nodes.len() - 1);
+        }
+        chain.push(to);
+        for pair in chain.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let duplicate =
+                roads.nodes[a].two_way.contains(&b) || roads.nodes[b].two_way.contains(&a);
+            if a != b && !duplicate {
+                roads.nodes[a].two_way.push(b);
+            }
+        }
+    }
+    fix_roads(&mut roads);
+    roads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Thinned skeleton of a mask drawn with `#` for walkable pixels
+    fn skeleton(rows: &[&str]) -> Skeleton {
+        let mask =
+            image::GrayAlphaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+                let walkable = rows[y as usize].as_bytes()[x as usize] == b'#';
+                image::LumaA([if walkable { 255 } else { 0 }, 255])
+            });
+        let mut skeleton = Skeleton::new(&mask, 128);
+        skeleton.thin();
+        skeleton
+    }
+
+    fn trace_pixels(skeleton: &Skeleton) -> (Vec<vec2<f32>>, Vec<Path>) {
+        trace(skeleton, |index| {
+            let (x, y) = skeleton.pos(index);
+            vec2(x as f32, y as f32)
+        })
+    }
+
+    fn degrees(centers: &[vec2<f32>], paths: &[Path]) -> Vec<usize> {
+        let mut degree = vec![0; centers.len()];
+        for path in paths {
+            degree[path.from] += 1;
+            degree[path.to] += 1;
+        }
+        degree
+    }
+
+    #[test]
+    fn thinning_leaves_one_pixel_wide_lines() {
+        let skeleton = skeleton(&[
+            "......................",
+            ".####################.",
+            ".####################.",
+            ".####################.",
+            "......................",
+        ]);
+        for index in (0..skeleton.pixels.len()).filter(|&index| skeleton.pixels[index]) {
+            assert!(skeleton.neighbors(index).len() <= 2);
+        }
+    }
+
+    #[test]
+    fn straight_bar_is_one_chain() {
+        let skeleton = skeleton(&[
+            "......................",
+            ".####################.",
+            ".####################.",
+            ".####################.",
+            "......................",
+        ]);
+        let (centers, paths) = trace_pixels(&skeleton);
+        assert_eq!(centers.len(), 2);
+        assert_eq!(paths.len(), 1);
+        assert_ne!(paths[0].from, paths[0].to);
+        assert!(paths[0].len() > 10.0);
+    }
+
+    #[test]
+    fn t_junction_is_one_node_of_degree_three() {
+        let mut rows = vec![
+            ".......................",
+            ".#####################.",
+            ".#####################.",
+            ".#####################.",
+        ];
+        rows.extend(["..........###.........."; 10]);
+        rows.push(".......................");
+        let skeleton = skeleton(&rows);
+        let (centers, paths) = trace_pixels(&skeleton);
+        assert_eq!(paths.len(), 3);
+        let mut degree = degrees(&centers, &paths);
+        degree.sort();
+        assert_eq!(degree, [1, 1, 1, 3]);
+    }
+
+    #[test]
+    fn ring_is_a_closed_loop() {
+        let mut rows = vec![
+            "................",
+            ".##############.",
+            ".##############.",
+            ".##############.",
+        ];
+        rows.extend([".###........###."; 8]);
+        rows.extend([
+            ".##############.",
+            ".##############.",
+            ".##############.",
+            "................",
+        ]);
+        let skeleton = skeleton(&rows);
+        let (centers, paths) = trace_pixels(&skeleton);
+        assert_eq!(centers.len(), 1);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].from, paths[0].to);
+        assert!(paths[0].len() > 30.0);
+    }
+
+    #[test]
+    fn subdivided_pieces_are_not_longer_than_spacing() {
+        let path = Path {
+            from: 0,
+            to: 1,
+            points: vec![
+                vec2(0.0, 0.0),
+                vec2(10.0, 0.0),
+                vec2(10.0, 7.0),
+                vec2(3.0, 9.0),
+            ],
+        };
+        for spacing in [0.5, 1.0, 2.9, 3.0, 4.5, 100.0] {
+            let points = subdivide(&path, spacing);
+            assert_eq!(points.len(), (path.len() / spacing).ceil() as usize - 1);
+            let chain: Vec<vec2<f32>> = std::iter::once(path.points[0])
+                .chain(points)
+                .chain(path.points.last().copied())
+                .collect();
+            for pair in chain.windows(2) {
+                assert!((pair[1] - pair[0]).len() <= spacing + 1e-3);
+            }
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use super::*;
+
+pub type LoadResult = Result<(serde_json::Value, Assets), Vec<String>>;
+
+/// Load config and assets, collecting every problem instead of panicking
+pub async fn load(geng: Geng) -> LoadResult {
+    let path = run_dir().join("assets");
+    let base_config: serde_json::Value = file::load_detect(path.join("config.toml"))
+        .await
+        .map_err(|e| vec![format!("config.toml: {e:#}")])?;
+    let mut assets: Assets = geng
+        .asset_manager()
+        .load(&path)
+        .await
+        .map_err(|e| vec![format!("Failed to load assets: {e:#}")])?;
+    let attractions = assets
+        .attractions
+        .iter()
+        .map(|attraction| (attraction.name.as_str(), attraction.config.pos));
+    assets.roads.resolve_legacy_entrances(attractions);
+    let errors = validate(&base_config, &assets);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok((base_config, assets))
+}
+
+#[derive(Default)]
+struct Errors(Vec<String>);
+
+impl Errors {
+    fn check(&mut self, ok: bool, file: &str, field: &str, message: impl std::fmt::Display) {
+        if !ok {
+            self.0.push(format!("{file}: `{field}` {message}"));
+        }
+    }
+
+    fn probability(&mut self, file: &str, field: &str, value: f64) {
+        self.check(
+            (0.0..=1.0).contains(&value),
+            file,
+            field,
+            format!("must be between 0 and 1, got {value}"),
+        );
+    }
+
+    fn positive(&mut self, file: &str, field: &str, value: f32) {
+        self.check(
+            value > 0.0,
+            file,
+            field,
+            format!("must be positive, got {value}"),
+        );
+    }
+
+    fn non_negative(&mut self, file: &str, field: &str, value: f64) {
+        self.check(
+            value >= 0.0,
+            file,
+            field,
+            format!("must not be negative, got {value}"),
+        );
+    }
+}
+
+fn validate(base_config: &serde_json::Value, assets: &Assets) -> Vec<String> {
+    let mut errors = Errors::default();
+
+    let difficulty = &assets.difficulty;
+    let file = "difficulty.toml";
+    errors.check(
+        !difficulty.presets.is_empty(),
+        file,
+        "presets",
+        "must not be empty",
+    );
+    errors.check(
+        difficulty.rounds_per_level > 0,
+        file,
+        "rounds_per_level",
+        "must be positive",
+    );
+    for preset in &difficulty.presets {
+        for key in preset.overrides.keys() {
+            errors.check(
+                base_config.get(key).is_some(),
+                file,
+                &format!("presets.{}.{key}", preset.name),
+                "is not a config.toml setting",
+            );
+        }
+    }
+    for (level, preset) in difficulty.presets.iter().enumerate() {
+        let file = format!("config.toml with {} preset", preset.name);
+        match difficulty.config(base_config, level) {
+            Ok(config) => validate_config(&mut errors, &file, &config, assets),
+            Err(e) => errors.0.push(format!("{file}: {e}")),
+        }
+    }
+
+    let node_count = assets.roads.nodes.len();
+    for (index, node) in assets.roads.nodes.iter().enumerate() {
+        for (field, edges) in [("connected", &node.connected), ("two_way", &node.two_way)] {
+            for &other in edges {
+                errors.check(
+                    other < node_count,
+                    "roads.json",
+                    &format!("nodes[{index}].{field}"),
+                    format!("refers to node {other}, but there are only {node_count} nodes"),
+                );
+            }
+        }
+        if let Some(name) = &node.attraction {
+            errors.check(
+                assets
+                    .attractions
+                    .iter()
+                    .any(|attraction| &attraction.name == name),
+                "roads.json",
+                &format!("nodes[{index}].attraction"),
+                format!("refers to unknown attraction {name:?}"),
+            );
+        }
+    }
+
+    let file = "editor.toml";
+    let editor = &assets.editor;
+    errors.positive(file, "grid_size", editor.grid_size);
+    errors.positive(
+        file,
+        "suggestion_min_distance",
+        editor.suggestion_min_distance,
+    );
+    errors.non_negative(
+        file,
+        "suggestion_attraction_margin",
+        editor.suggestion_attraction_margin as f64,
+    );
+
+    for attraction in &assets.attractions {
+        validate_attraction(
+            &mut errors,
+            &format!("attractions/{}/config.toml", attraction.name),
+            &attraction.config,
+        );
+    }
+
+    errors.check(
+        !assets.items.is_empty(),
+        "items/_list.ron",
+        "items",
+        "must not be empty",
+    );
+    errors.check(
+        !assets.crabs.is_empty(),
+        "crabs/_list.ron",
+        "crabs",
+        "must not be empty",
+    );
+    errors.check(
+        assets
+            .crabs
+            .iter()
+            .any(|crab| crab.config.spawn_weight > 0.0),
+        "crabs/*/config.toml",
+        "spawn_weight",
+        "must be positive for at least one crab",
+    );
+    for crab in &assets.crabs {
+        validate_crab(
+            &mut errors,
+            &format!("crabs/{}/config.toml", crab.name),
+            &crab.config,
+        );
+    }
+
+    errors.0
+}
+
+fn validate_config(errors: &mut Errors, file: &str, config: &Config, assets: &Assets) {
+    errors.positive(file, "click_radius", config.click_radius);
+    errors.positive(file, "min_fov", config.min_fov);
+    errors.check(
+        config.min_fov <= config.max_fov,
+        file,
+        "min_fov",
+        format!(
+            "({}) must not exceed max_fov ({})",
+            config.min_fov, config.max_fov
+        ),
+    );
+    errors.check(
+        (config.min_fov..=config.max_fov).contains(&config.default_fov),
+        file,
+        "default_fov",
+        format!(
+            "({}) must be between min_fov and max_fov",
+            config.default_fov
+        ),
+    );
+    errors.positive(file, "zoom_speed", config.zoom_speed);
+    errors.positive(file, "crab_speed", config.crab_speed);
+    errors.non_negative(file, "day_length", config.day_length as f64);
+    errors.probability(file, "night_darkness", config.night_darkness as f64);
+    errors.non_negative(file, "lamp_light_radius", config.lamp_light_radius as f64);
+    errors.non_negative(file, "cabin_light_radius", config.cabin_light_radius as f64);
+    errors.positive(
+        file,
+        "rain_crab_speed_multiplier",
+        config.rain_crab_speed_multiplier,
+    );
+    errors.non_negative(file, "rain_rate", config.rain_rate as f64);
+    errors.non_negative(file, "hint_duration", config.hint_duration as f64);
+    errors.non_negative(file, "hint_cooldown", config.hint_cooldown as f64);
+    errors.non_negative(file, "road_width", config.road_width as f64);
+    errors.non_negative(
+        file,
+        "crab_speed_variation",
+        config.crab_speed_variation as f64,
+    );
+    errors.check(
+        config.crab_speed_variation < 1.0,
+        file,
+        "crab_speed_variation",
+        "must be less than 1",
+    );
+    errors.non_negative(
+        file,
+        "crab_scale_variation",
+        config.crab_scale_variation as f64,
+    );
+    errors.check(
+        config.crab_scale_variation < 1.0,
+        file,
+        "crab_scale_variation",
+        "must be less than 1",
+    );
+    errors.positive(file, "crab_acceleration", config.crab_acceleration);
+    errors.positive(file, "crab_braking", config.crab_braking);
+    errors.check(
+        config.crab_min_gap <= config.crab_desired_gap,
+        file,
+        "crab_min_gap",
+        format!(
+            "({}) must not exceed crab_desired_gap ({})",
+            config.crab_min_gap, config.crab_desired_gap
+        ),
+    );
+    errors.non_negative(file, "crab_min_idle_time", config.crab_min_idle_time as f64);
+    errors.check(
+        config.crab_min_idle_time <= config.crab_max_idle_time,
+        file,
+        "crab_min_idle_time",
+        format!(
+            "({}) must not exceed crab_max_idle_time ({})",
+            config.crab_min_idle_time, config.crab_max_idle_time
+        ),
+    );
+    errors.check(
+        config.target_min_count <= config.target_max_count,
+        file,
+        "target_min_count",
+        format!(
+            "({}) must not exceed target_max_count ({})",
+            config.target_min_count, config.target_max_count
+        ),
+    );
+    errors.check(
+        config.types_to_find <= assets.items.len(),
+        file,
+        "types_to_find",
+        format!(
+            "({}) must not exceed the number of item types ({})",
+            config.types_to_find,
+            assets.items.len()
+        ),
+    );
+    for (field, value) in [
+        (
+            "crab_hold_item_probability",
+            config.crab_hold_item_probability,
+        ),
+        (
+            "crab_hold_double_item_probability",
+            config.crab_hold_double_item_probability,
+        ),
+        ("crab_idle_probability", config.crab_idle_probability),
+        (
+            "crab_idle_spot_probability",
+            config.crab_idle_spot_probability,
+        ),
+        ("crab_ride_probability", config.crab_ride_probability),
+        ("crab_exit_probability", config.crab_exit_probability),
+        (
+            "target_in_hand_probability",
+            config.target_in_hand_probability,
+        ),
+        (
+            "target_in_cabin_probability",
+            config.target_in_cabin_probability,
+        ),
+        ("cabin_item_probability", config.cabin_item_probability),
+        ("rain_probability", config.rain_probability),
+        (
+            "rain_umbrella_probability",
+            config.rain_umbrella_probability,
+        ),
+        ("dust_probability", config.dust_probability),
+    ] {
+        errors.probability(file, field, value);
+    }
+    for (field, value) in [
+        ("crab_drop_item_rate", config.crab_drop_item_rate),
+        ("crab_pick_up_item_rate", config.crab_pick_up_item_rate),
+        ("crab_trade_items_rate", config.crab_trade_items_rate),
+        ("crab_arrival_rate", config.crab_arrival_rate),
+    ] {
+        errors.non_negative(file, field, value);
+    }
+}
+
+fn validate_attraction(errors: &mut Errors, file: &str, config: &attraction::AttractionConfig) {
+    errors.check(config.cabins > 0, file, "cabins", "must be positive");
+    errors.positive(file, "crab_scale", config.crab_scale);
+    errors.positive(file, "item_scale", config.item_scale);
+    errors.check(config.ride_laps > 0, file, "ride_laps", "must be positive");
+    match config.motion {
+        attraction::Motion::Wheel { radius, .. } => errors.positive(file, "radius", radius),
+        attraction::Motion::Carousel {
+            radius,
+            perspective,
+            ..
+        } => {
+            errors.positive(file, "radius.x", radius.x);
+            errors.positive(file, "radius.y", radius.y);
+            errors.non_negative(file, "perspective", perspective as f64);
+            errors.check(
+                perspective < 1.0,
+                file,
+                "perspective",
+                "must be less than 1",
+            );
+        }
+        attraction::Motion::Swing {
+            length,
+            amplitude,
+            freq,
+            ..
+        } => {
+            errors.positive(file, "length", length);
+            errors.positive(file, "freq", freq);
+            errors.non_negative(file, "amplitude", amplitude as f64);
+        }
+    }
+}
+
+fn validate_crab(errors: &mut Errors, file: &str, config: &CrabConfig) {
+    errors.check(
+        config.spawn_weight.is_finite() && config.spawn_weight >= 0.0,
+        file,
+        "spawn_weight",
+        format!("must be a non-negative number, got {}", config.spawn_weight),
+    );
+    errors.positive(file, "scale", config.scale);
+    if let Some(speed) = config.speed {
+        errors.positive(file, "speed", speed);
+    }
+    if let Some(variation) = config.speed_variation {
+        errors.non_negative(file, "speed_variation", variation as f64);
+        errors.check(
+            variation < 1.0,
+            file,
+            "speed_variation",
+            "must be less than 1",
+        );
+    }
+    if let Some(variation) = config.scale_variation {
+        errors.non_negative(file, "scale_variation", variation as f64);
+        errors.check(
+            variation < 1.0,
+            file,
+            "scale_variation",
+            "must be less than 1",
+        );
+    }
+    if let Some(sheet) = &config.sprite_sheet {
+        errors.check(
+            sheet.frame_size.x > 0 && sheet.frame_size.y > 0,
+            file,
+            "sprite_sheet.frame_size",
+            "must be positive",
+        );
+        for (kind, clip) in &sheet.clips {
+            errors.check(
+                clip.frames > 0,
+                file,
+                &format!("sprite_sheet.clips.{}.frames", kind.name()),
+                "must be positive",
+            );
+        }
+    }
+}